@@ -8,8 +8,8 @@ pub fn define_ast(output_dir: &str, base_name: &str, types: Vec<&str>) -> Result
     create_dir_all(directory.clone())?;
     let file_path = directory.join(format!("{}{}", base_name, ".java"));
     let mut file_buffer = File::create(file_path)?;
-    file_buffer.write(b"package com.craftinginterpreters.lox;\n\n")?;
-    file_buffer.write(b"import java.util.List;\n\n")?;
+    file_buffer.write_all(b"package com.craftinginterpreters.lox;\n\n")?;
+    file_buffer.write_all(b"import java.util.List;\n\n")?;
     file_buffer.write_fmt(format_args!("abstract class {} {{\n", base_name))?;
 
     define_visitor(&mut file_buffer, base_name, &types)?;
@@ -20,12 +20,12 @@ pub fn define_ast(output_dir: &str, base_name: &str, types: Vec<&str>) -> Result
         let field_list = type_split.next().unwrap().trim();
         define_type(&mut file_buffer, base_name, class_name, field_list)?;
     }
-    file_buffer.write(b"}\n\n")?;
+    file_buffer.write_all(b"}\n\n")?;
     Ok(())
 }
 
 fn define_visitor(file_buffer: &mut File, base_name: &str, types: &Vec<&str>) -> Result<()> {
-    file_buffer.write(b"  interface Visitor<R> {\n")?;
+    file_buffer.write_all(b"  interface Visitor<R> {\n")?;
 
     for lox_type in types {
         let type_name = lox_type.split(":").next().unwrap().trim();
@@ -38,7 +38,7 @@ fn define_visitor(file_buffer: &mut File, base_name: &str, types: &Vec<&str>) ->
         ))?;
     }
 
-    file_buffer.write(b"  }\n\n")?;
+    file_buffer.write_all(b"  }\n\n")?;
     Ok(())
 }
 
@@ -62,22 +62,22 @@ fn define_type(
         let name = field.split(" ").nth(1).unwrap();
         file_buffer.write_fmt(format_args!("      this.{} = {};\n", name, name))?;
     }
-    file_buffer.write(b"    }\n\n")?;
+    file_buffer.write_all(b"    }\n\n")?;
 
     // visitor pattern
-    file_buffer.write(b"    @Override\n")?;
-    file_buffer.write(b"    <R> R accept(Visitor<R> visitor) {\n")?;
+    file_buffer.write_all(b"    @Override\n")?;
+    file_buffer.write_all(b"    <R> R accept(Visitor<R> visitor) {\n")?;
     file_buffer.write_fmt(format_args!(
         "      return visitor.visit{}{}(this);\n",
         class_name, base_name
     ))?;
-    file_buffer.write(b"    }\n\n")?;
+    file_buffer.write_all(b"    }\n\n")?;
 
     // fields
     for field in fields {
         file_buffer.write_fmt(format_args!("    final {};\n", field))?;
     }
 
-    file_buffer.write(b"  }\n\n")?;
+    file_buffer.write_all(b"  }\n\n")?;
     Ok(())
 }
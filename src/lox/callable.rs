@@ -2,11 +2,16 @@ use std::{
     cell::RefCell,
     collections::HashMap,
     fmt::{Debug, Display},
+    ops::RangeInclusive,
     rc::Rc,
 };
 
 use super::{
-    environment::Environment, instance::Instance, interpreter::Interpreter, stmt::Stmt,
+    environment::Environment,
+    generator::{suspends, yields, Generator},
+    instance::Instance,
+    interpreter::{Interpreter, Unwind},
+    stmt::Stmt,
     value::Value,
 };
 
@@ -19,12 +24,16 @@ pub trait Callable {
 pub struct NativeFunction {
     name: String,
     arity: usize,
-    callable: fn(&Interpreter, Vec<Value>) -> Result<Value, String>,
+    // how many of the last arguments can be left out, the native fills them in
+    optional: usize,
+    callable: fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>,
+    // the value a native method was looked up on, passed in ahead of the arguments
+    receiver: Option<Box<Value>>,
 }
 
 impl Display for NativeFunction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(fn {} {})", self.name, self.arity)
+        write!(f, "<native fn>")
     }
 }
 
@@ -44,14 +53,31 @@ impl NativeFunction {
     pub fn new(
         name: String,
         arity: usize,
-        callable: fn(&Interpreter, Vec<Value>) -> Result<Value, String>,
+        callable: fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>,
     ) -> Self {
         Self {
             name,
             arity,
+            optional: 0,
             callable,
+            receiver: None,
         }
     }
+
+    pub fn with_optional(mut self, optional: usize) -> Self {
+        self.optional = optional;
+        self
+    }
+
+    // the numbers of arguments it can be called with
+    pub fn arities(&self) -> RangeInclusive<usize> {
+        self.arity - self.optional..=self.arity
+    }
+
+    pub fn bind(mut self, receiver: Value) -> Self {
+        self.receiver = Some(Box::new(receiver));
+        self
+    }
 }
 
 impl Callable for NativeFunction {
@@ -60,14 +86,30 @@ impl Callable for NativeFunction {
     }
 
     fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, String> {
-        if arguments.len() != self.arity() {
+        if !self.arities().contains(&arguments.len()) {
             return Err(format!(
                 "Expected {} arguments but got {} arguments",
-                self.arity(),
+                describe_arities(&self.arities()),
                 arguments.len()
             ));
         }
-        (self.callable)(interpreter, arguments)
+        match &self.receiver {
+            Some(receiver) => {
+                let mut arguments = arguments;
+                arguments.insert(0, (**receiver).clone());
+                (self.callable)(interpreter, arguments)
+            }
+            None => (self.callable)(interpreter, arguments),
+        }
+    }
+}
+
+// "2" or "2 to 3"
+pub fn describe_arities(arities: &RangeInclusive<usize>) -> String {
+    if arities.start() == arities.end() {
+        arities.start().to_string()
+    } else {
+        format!("{} to {}", arities.start(), arities.end())
     }
 }
 
@@ -80,7 +122,7 @@ pub struct Function {
 
 impl Function {
     pub fn new(declaration: Stmt, closure: Rc<RefCell<Environment>>, is_initializer: bool) -> Self {
-        if let Stmt::FunctionDeclaration(_, _, _) = declaration {
+        if let Stmt::FunctionDeclaration(_, _, _, _) = declaration {
             Self {
                 declaration,
                 closure,
@@ -102,14 +144,55 @@ impl Function {
     }
 }
 
+impl Function {
+    // whether a task calling the function steps through its body, as it could
+    // stop in it; generators and initializers give their value straight away
+    pub fn suspends(&self) -> bool {
+        if let Stmt::FunctionDeclaration(_, _, body, _) = &self.declaration {
+            !self.is_initializer && !yields(body) && suspends(body)
+        } else {
+            panic!()
+        }
+    }
+
+    // the body run as a task, for spawn and for calls a task steps through
+    pub fn task(&self, arguments: Vec<Value>) -> Result<(String, Generator), String> {
+        let environment = self.environment(arguments)?;
+        if let Stmt::FunctionDeclaration(name, _, body, _) = &self.declaration {
+            let body = Generator::task(
+                name.clone(),
+                body.to_vec(),
+                Rc::new(RefCell::new(environment)),
+            );
+            Ok((name.lexeme.clone(), body))
+        } else {
+            panic!()
+        }
+    }
+
+    // the parameters bound to the arguments, in a scope of the closure
+    fn environment(&self, arguments: Vec<Value>) -> Result<Environment, String> {
+        if arguments.len() != self.arity() {
+            return Err(format!(
+                "Expected {} arguments but got {} arguments",
+                self.arity(),
+                arguments.len()
+            ));
+        }
+        let mut environment = Environment::new(HashMap::new(), Some(self.closure.clone()));
+        if let Stmt::FunctionDeclaration(_, parameters, _, _) = &self.declaration {
+            for (parameter, argument) in parameters.iter().zip(arguments) {
+                environment.define(parameter.lexeme.clone(), argument);
+            }
+        }
+        Ok(environment)
+    }
+}
+
 impl Display for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Stmt::FunctionDeclaration(name, parameters, _) = &self.declaration {
-            write!(f, "(fn {}(", name.lexeme)?;
-            for parameter in parameters {
-                write!(f, " {}", parameter)?;
-            }
-            write!(f, "))")
+        if let Stmt::FunctionDeclaration(name, _, _, _) = &self.declaration {
+            write!(f, "<fn {}>", name.lexeme)
         } else {
             panic!()
         }
@@ -124,7 +207,7 @@ impl PartialEq for Function {
 
 impl Debug for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Stmt::FunctionDeclaration(name, parameters, _) = &self.declaration {
+        if let Stmt::FunctionDeclaration(name, parameters, _, _) = &self.declaration {
             write!(f, "(fn {}(", name.lexeme)?;
             for parameter in parameters {
                 write!(f, " {}", parameter)?;
@@ -138,7 +221,7 @@ impl Debug for Function {
 
 impl Callable for Function {
     fn arity(&self) -> usize {
-        if let Stmt::FunctionDeclaration(_, parameters, _) = &self.declaration {
+        if let Stmt::FunctionDeclaration(_, parameters, _, _) = &self.declaration {
             parameters.len()
         } else {
             panic!()
@@ -146,30 +229,28 @@ impl Callable for Function {
     }
 
     fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, String> {
-        if arguments.len() != self.arity() {
-            return Err(format!(
-                "Expected {} arguments but got {} arguments",
-                self.arity(),
-                arguments.len()
-            ));
-        }
-        let mut environment = Environment::new(HashMap::new(), Some(self.closure.clone()));
-        if let Stmt::FunctionDeclaration(_, parameters, body) = &self.declaration {
-            for (parameter, argument) in parameters.iter().zip(arguments) {
-                environment.define(parameter.lexeme.clone(), argument);
+        let environment = self.environment(arguments)?;
+        if let Stmt::FunctionDeclaration(name, _, body, _) = &self.declaration {
+            if yields(body) {
+                return Ok(Value::Generator(Generator::new(
+                    name.clone(),
+                    body.to_vec(),
+                    Rc::new(RefCell::new(environment)),
+                )));
             }
-            match interpreter.execute_block(body.to_vec(), environment) {
-                Ok(_) => Ok(if self.is_initializer {
-                    self.closure.borrow().get_at(0, "this".to_string())?
-                } else {
-                    Value::Nil
-                }),
-                Err(value) => {
-                    if self.is_initializer {
-                        self.closure.borrow().get_at(0, "this".to_string())
-                    } else {
-                        Ok(value)
-                    }
+            interpreter.push_frame(name.lexeme.clone(), name.line);
+            let result = interpreter.execute_block(body.to_vec(), environment);
+            interpreter.pop_frame();
+            match result {
+                // initializers always hand back the instance, even from an early return
+                Ok(_) | Err(Unwind::Return(_)) if self.is_initializer => {
+                    self.closure.borrow().get_at(0, "this".to_string())
+                }
+                Ok(_) => Ok(Value::Nil),
+                Err(Unwind::Return(value)) => Ok(value),
+                Err(Unwind::Error(err)) => Err(err),
+                Err(Unwind::Break | Unwind::Continue) => {
+                    unreachable!("the resolver keeps break and continue inside loops")
                 }
             }
         } else {
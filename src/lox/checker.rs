@@ -0,0 +1,1022 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use super::{
+    expr::Expr,
+    generator::yields,
+    stmt::{MethodKind, Signature, Stmt},
+    token::Token,
+    token_type::TokenType,
+};
+
+// what the checker knows about a value before the program runs
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    // nothing is known, so anything goes
+    Any,
+    Nil,
+    Bool,
+    Num,
+    Str,
+    // parameter and return types, unknown for values annotated as plain `fun`
+    Function(Option<(Vec<Type>, Box<Type>)>),
+    // the class itself, calling it makes an instance
+    Class(String),
+    Instance(String),
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the same names annotations use
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Nil => write!(f, "nil"),
+            Type::Bool => write!(f, "bool"),
+            Type::Num => write!(f, "num"),
+            Type::Str => write!(f, "str"),
+            Type::Function(_) => write!(f, "fun"),
+            Type::Class(name) => write!(f, "class {}", name),
+            Type::Instance(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClassInfo {
+    superclass: Option<String>,
+    // getters and setters are read and written like fields
+    fields: HashMap<String, Type>,
+    methods: HashMap<String, Type>,
+    statics: HashMap<String, Type>,
+}
+
+// the methods every class and trait declares and where it inherits from, gathered
+// before checking so subclasses declared later are known
+#[derive(Default)]
+struct Declared {
+    superclass: Option<String>,
+    traits: Vec<String>,
+    methods: HashSet<String>,
+}
+
+// checks annotated and inferred types before the program runs: operands,
+// argument counts and types, calls on things that can't be called, and
+// assignments and returns against annotations
+pub struct Checker {
+    // innermost last, the globals first
+    scopes: Vec<HashMap<String, Type>>,
+    classes: HashMap<String, ClassInfo>,
+    traits: HashMap<String, ClassInfo>,
+    // every class declared anywhere, so annotations can name classes declared later
+    class_names: HashSet<String>,
+    // classes and traits by name, to find which methods a subclass overrides
+    declared: HashMap<String, Declared>,
+    // names assigned to anywhere or declared twice as globals, the type of their
+    // first value can't be trusted unless they were annotated
+    assigned: HashSet<String>,
+    // names read anywhere outside their own function, functions that aren't may
+    // never run so their bodies aren't held against the program
+    used: HashSet<String>,
+    collecting: Vec<String>,
+    // name and return type of the function being checked
+    current_function: Option<(String, Type)>,
+    current_class: Option<String>,
+    errors: Vec<String>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        let mut globals = HashMap::new();
+        globals.insert("clock".to_string(), function(vec![], Type::Num));
+        globals.insert(
+            "instanceof".to_string(),
+            function(vec![Type::Any, Type::Any], Type::Bool),
+        );
+        globals.insert("classOf".to_string(), function(vec![Type::Any], Type::Any));
+        globals.insert("sleep".to_string(), function(vec![Type::Num], Type::Any));
+        globals.insert("channel".to_string(), function(vec![], Type::Any));
+        // range takes 2 or 3 numbers, which one signature can't say
+        globals.insert("range".to_string(), Type::Function(None));
+        for assertion in ["assert", "assert_eq"] {
+            globals.insert(
+                assertion.to_string(),
+                function(vec![Type::Any, Type::Any], Type::Nil),
+            );
+        }
+        Self {
+            scopes: vec![globals],
+            classes: HashMap::new(),
+            traits: HashMap::new(),
+            class_names: HashSet::new(),
+            declared: HashMap::new(),
+            assigned: HashSet::new(),
+            used: HashSet::new(),
+            collecting: Vec::new(),
+            current_function: None,
+            current_class: None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        let mut globals = HashSet::new();
+        for stmt in stmts {
+            self.collect_statement(stmt);
+            // declaring a global again is as good as assigning to it
+            let name = match stmt {
+                Stmt::VariableDeclaration(name, _, _)
+                | Stmt::ClassDeclaration(name, _, _, _)
+                | Stmt::TraitDeclaration(name, _) => &name.lexeme,
+                Stmt::FunctionDeclaration(name, _, _, _) => &name.lexeme,
+                _ => continue,
+            };
+            if !globals.insert(name.clone()) {
+                self.assigned.insert(name.clone());
+            }
+        }
+        self.statements(stmts);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.join("\n"))
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: String) {
+        self.errors
+            .push(format!("Line {}: {}", token.line, message));
+    }
+
+    // finds the class names and assigned names up front
+    fn collect_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(_, stmts) => {
+                for stmt in stmts {
+                    self.collect_statement(stmt);
+                }
+            }
+            Stmt::Expression(_, expr)
+            | Stmt::Print(_, expr)
+            | Stmt::Return(_, expr)
+            | Stmt::Yield(_, expr) => self.collect_expression(expr),
+            Stmt::Break(_) | Stmt::Continue(_) => (),
+            Stmt::VariableDeclaration(_, _, initializer) => self.collect_expression(initializer),
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                self.collect_expression(condition);
+                self.collect_statement(then_branch);
+                if let Some(else_branch) = &**else_branch {
+                    self.collect_statement(else_branch);
+                }
+            }
+            Stmt::While(_, condition, body) => {
+                self.collect_expression(condition);
+                self.collect_statement(body);
+            }
+            Stmt::ForIn(_, _, iterable, body) => {
+                self.collect_expression(iterable);
+                self.collect_statement(body);
+            }
+            Stmt::FunctionDeclaration(name, _, body, _) => {
+                self.collecting.push(name.lexeme.clone());
+                for stmt in body {
+                    self.collect_statement(stmt);
+                }
+                self.collecting.pop();
+            }
+            Stmt::ClassDeclaration(name, superclass, traits, members) => {
+                self.class_names.insert(name.lexeme.clone());
+                let named = |expr: &Expr| match expr {
+                    Expr::Variable(name) => Some(name.lexeme.clone()),
+                    _ => None,
+                };
+                let declared = Declared {
+                    superclass: superclass.as_ref().and_then(named),
+                    traits: traits.iter().filter_map(named).collect(),
+                    methods: method_names(members),
+                };
+                self.declared.insert(name.lexeme.clone(), declared);
+                for member in members {
+                    self.collect_statement(member);
+                }
+            }
+            Stmt::TraitDeclaration(name, members) => {
+                let declared = Declared {
+                    methods: method_names(members),
+                    ..Declared::default()
+                };
+                self.declared.insert(name.lexeme.clone(), declared);
+                for member in members {
+                    self.collect_statement(member);
+                }
+            }
+        }
+    }
+
+    fn collect_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign(name, value) => {
+                self.assigned.insert(name.lexeme.clone());
+                self.collect_expression(value);
+            }
+            Expr::Binary(left, _, right)
+            | Expr::Logical(left, _, right)
+            | Expr::Index(left, _, right) => {
+                self.collect_expression(left);
+                self.collect_expression(right);
+            }
+            Expr::Grouping(inner)
+            | Expr::Urnary(_, inner)
+            | Expr::Await(_, inner)
+            | Expr::Spawn(_, inner)
+            | Expr::Get(inner, _)
+            | Expr::OptionalGet(inner, _) => self.collect_expression(inner),
+            Expr::Conditional(condition, _, then_branch, else_branch) => {
+                self.collect_expression(condition);
+                self.collect_expression(then_branch);
+                self.collect_expression(else_branch);
+            }
+            Expr::Set(object, _, value) | Expr::CompoundSet(object, _, _, value) => {
+                self.collect_expression(object);
+                self.collect_expression(value);
+            }
+            Expr::CompoundAssign(_, _, value) | Expr::Postfix(value, _) => {
+                self.collect_expression(value)
+            }
+            Expr::Call(callee, _, arguments) => {
+                self.collect_expression(callee);
+                for argument in arguments {
+                    self.collect_expression(argument);
+                }
+            }
+            Expr::Variable(name) => {
+                if self.collecting.last() != Some(&name.lexeme) {
+                    self.used.insert(name.lexeme.clone());
+                }
+            }
+            Expr::StringLiteral(_)
+            | Expr::NumberLiteral(..)
+            | Expr::IntLiteral(..)
+            | Expr::NilLiteral
+            | Expr::TrueLiteral
+            | Expr::FalseLiteral
+            | Expr::This(_)
+            | Expr::Super(_, _) => (),
+        }
+    }
+
+    fn statements(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(_, stmts) => {
+                self.scopes.push(HashMap::new());
+                self.statements(stmts);
+                self.scopes.pop();
+            }
+            Stmt::Expression(_, expr) | Stmt::Print(_, expr) | Stmt::Yield(_, expr) => {
+                self.expression(expr);
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => (),
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = &**else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While(_, condition, body) => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            Stmt::ForIn(_, name, iterable, body) => {
+                // only strings are known to give strings, anything else could give anything
+                let element = match self.expression(iterable) {
+                    Type::Str => Type::Str,
+                    _ => Type::Any,
+                };
+                self.scopes.push(HashMap::new());
+                self.define(name, element, false);
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Stmt::VariableDeclaration(name, annotation, initializer) => {
+                let value = self.expression(initializer);
+                let declared = match annotation {
+                    Some(annotation) => {
+                        let declared = self.annotation(annotation);
+                        if !accepts(&declared, &value, &self.classes) {
+                            self.error(
+                                name,
+                                format!(
+                                    "Can't assign {} to '{}' of type {}",
+                                    value, name.lexeme, declared
+                                ),
+                            );
+                        }
+                        declared
+                    }
+                    // a variable with no value yet is only a placeholder
+                    None if value == Type::Nil => Type::Any,
+                    None => value,
+                };
+                self.define(name, declared, annotation.is_some());
+            }
+            Stmt::FunctionDeclaration(name, parameters, body, signature) => {
+                let function = self.signature(signature);
+                // defined first, so the body can call itself
+                self.define(name, function.clone(), false);
+                let reported = self.errors.len();
+                self.function(name, parameters, body, &function);
+                if !self.used.contains(&name.lexeme) {
+                    self.errors.truncate(reported);
+                }
+            }
+            Stmt::Return(keyword, value) => {
+                let value = self.expression(value);
+                if let Some((name, returns)) = self.current_function.clone() {
+                    if !accepts(&returns, &value, &self.classes) {
+                        self.error(
+                            keyword,
+                            format!(
+                                "Can't return {} from '{}', which returns {}",
+                                value, name, returns
+                            ),
+                        );
+                    }
+                }
+            }
+            Stmt::ClassDeclaration(name, superclass, traits, members) => {
+                let mut info = ClassInfo::default();
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                    // only classes declared before this one can be its superclass
+                    if let Expr::Variable(superclass) = superclass {
+                        if self.classes.contains_key(&superclass.lexeme) {
+                            info.superclass = Some(superclass.lexeme.clone());
+                        }
+                    }
+                }
+                let method_types = self.members(&mut info, members);
+                // the class's own members come first, traits fill in the rest
+                for mixed in traits {
+                    self.expression(mixed);
+                    let mixed = match mixed {
+                        Expr::Variable(mixed) => self.traits.get(&mixed.lexeme),
+                        _ => None,
+                    };
+                    if let Some(mixed) = mixed {
+                        for (own, from) in [
+                            (&mut info.fields, &mixed.fields),
+                            (&mut info.methods, &mixed.methods),
+                            (&mut info.statics, &mixed.statics),
+                        ] {
+                            for (member, member_type) in from {
+                                own.entry(member.clone())
+                                    .or_insert_with(|| member_type.clone());
+                            }
+                        }
+                    }
+                }
+                self.classes.insert(name.lexeme.clone(), info);
+                self.define(name, Type::Class(name.lexeme.clone()), false);
+
+                let enclosing_class = self.current_class.replace(name.lexeme.clone());
+                self.member_bodies(&name.lexeme, members, method_types);
+                self.current_class = enclosing_class;
+            }
+            // a trait's methods could end up in any class, so this is any in them
+            Stmt::TraitDeclaration(name, members) => {
+                let mut info = ClassInfo::default();
+                let method_types = self.members(&mut info, members);
+                self.traits.insert(name.lexeme.clone(), info);
+                self.define(name, Type::Any, false);
+
+                let enclosing_class = self.current_class.take();
+                self.member_bodies(&name.lexeme, members, method_types);
+                self.current_class = enclosing_class;
+            }
+        }
+    }
+
+    // the types of a class's members, returning the type of each method in order
+    // so each signature is only looked at once, and unknown types are reported once
+    fn members(&mut self, info: &mut ClassInfo, members: &[Stmt]) -> Vec<Type> {
+        let mut method_types = Vec::new();
+        for member in members {
+            match member {
+                Stmt::VariableDeclaration(field, annotation, _) => {
+                    let field_type = match annotation {
+                        Some(annotation) => self.annotation(annotation),
+                        None => Type::Any,
+                    };
+                    info.fields.insert(field.lexeme.clone(), field_type);
+                }
+                Stmt::FunctionDeclaration(method, _, _, signature) => {
+                    let method_type = self.signature(signature);
+                    let member = method.lexeme.clone();
+                    match (&signature.kind, &method_type) {
+                        (MethodKind::Method, _) => {
+                            info.methods.insert(member, method_type.clone());
+                        }
+                        (MethodKind::Static, _) => {
+                            info.statics.insert(member, method_type.clone());
+                        }
+                        (MethodKind::Getter, Type::Function(Some((_, returns)))) => {
+                            info.fields.insert(member, *returns.clone());
+                        }
+                        // a getter decides the type when there is one
+                        (MethodKind::Setter, Type::Function(Some((parameters, _)))) => {
+                            info.fields.entry(member).or_insert(parameters[0].clone());
+                        }
+                        _ => (),
+                    }
+                    method_types.push(method_type);
+                }
+                _ => (),
+            }
+        }
+        method_types
+    }
+
+    fn member_bodies(&mut self, class: &str, members: &[Stmt], method_types: Vec<Type>) {
+        let mut method_types = method_types.into_iter();
+        for member in members {
+            match member {
+                Stmt::VariableDeclaration(field, _, initializer) => {
+                    let value = self.expression(initializer);
+                    if let Some(declared) = self.field(class, &field.lexeme) {
+                        if !accepts(&declared, &value, &self.classes) {
+                            self.error(
+                                field,
+                                format!(
+                                    "Can't assign {} to field '{}' of type {}",
+                                    value, field.lexeme, declared
+                                ),
+                            );
+                        }
+                    }
+                }
+                Stmt::FunctionDeclaration(method, parameters, body, _) => {
+                    let method_type = method_types.next().unwrap_or(Type::Any);
+                    self.function(method, parameters, body, &method_type);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn function(&mut self, name: &Token, parameters: &[Token], body: &[Stmt], function: &Type) {
+        let (parameter_types, returns) = match function {
+            Type::Function(Some((parameter_types, returns))) => {
+                (parameter_types.clone(), *returns.clone())
+            }
+            _ => (vec![Type::Any; parameters.len()], Type::Any),
+        };
+        // a generator's returns only end it, whatever it was declared to give
+        let returns = if yields(body) { Type::Any } else { returns };
+        let enclosing_function = self
+            .current_function
+            .replace((name.lexeme.clone(), returns));
+        self.scopes.push(HashMap::new());
+        for (parameter, parameter_type) in parameters.iter().zip(parameter_types) {
+            // parameters can always be reassigned, so they're taken at their annotation
+            self.define(parameter, parameter_type, true);
+        }
+        self.statements(body);
+        self.scopes.pop();
+        self.current_function = enclosing_function;
+    }
+
+    // the type of a declared function, unannotated parts are any
+    fn signature(&mut self, signature: &Signature) -> Type {
+        let parameters = signature
+            .parameters
+            .iter()
+            .map(|annotation| match annotation {
+                Some(annotation) => self.annotation(annotation),
+                None => Type::Any,
+            })
+            .collect();
+        let returns = match &signature.returns {
+            Some(annotation) => self.annotation(annotation),
+            None => Type::Any,
+        };
+        function(parameters, returns)
+    }
+
+    fn annotation(&mut self, annotation: &Token) -> Type {
+        match annotation.lexeme.as_str() {
+            "any" => Type::Any,
+            "nil" => Type::Nil,
+            "bool" => Type::Bool,
+            "num" => Type::Num,
+            "str" => Type::Str,
+            "fun" => Type::Function(None),
+            name if self.class_names.contains(name) => Type::Instance(name.to_string()),
+            name => {
+                self.error(annotation, format!("Unknown type '{}'", name));
+                Type::Any
+            }
+        }
+    }
+
+    // annotated names keep their type; otherwise names that are assigned to
+    // somewhere could end up holding anything
+    fn define(&mut self, name: &Token, declared: Type, annotated: bool) {
+        let declared = if annotated || !self.assigned.contains(&name.lexeme) {
+            declared
+        } else {
+            Type::Any
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), declared);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Type {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .unwrap_or(Type::Any)
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::StringLiteral(_) => Type::Str,
+            Expr::NumberLiteral(..) | Expr::IntLiteral(..) => Type::Num,
+            Expr::NilLiteral => Type::Nil,
+            Expr::TrueLiteral | Expr::FalseLiteral => Type::Bool,
+            Expr::Grouping(inner) => self.expression(inner),
+            Expr::Variable(name) => self.lookup(&name.lexeme),
+            Expr::Assign(name, value) => {
+                let value = self.expression(value);
+                let declared = self.lookup(&name.lexeme);
+                if !accepts(&declared, &value, &self.classes) {
+                    self.error(
+                        name,
+                        format!(
+                            "Can't assign {} to '{}' of type {}",
+                            value, name.lexeme, declared
+                        ),
+                    );
+                }
+                value
+            }
+            // the call is checked as usual, though spawn gives a task rather than its result
+            Expr::Await(_, inner) | Expr::Spawn(_, inner) => {
+                self.expression(inner);
+                Type::Any
+            }
+            Expr::Urnary(operator, right) => {
+                let right = self.expression(right);
+                if operator.token_type == TokenType::Bang {
+                    return Type::Bool;
+                }
+                if !is_number(&right) {
+                    self.error(
+                        operator,
+                        format!(
+                            "Operand of '{}' must be a number, got {}",
+                            operator.lexeme, right
+                        ),
+                    );
+                }
+                Type::Num
+            }
+            Expr::Binary(left, operator, right) => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                self.binary(operator, left, right)
+            }
+            Expr::Logical(left, _, right) => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                if left == right {
+                    left
+                } else {
+                    Type::Any
+                }
+            }
+            Expr::Call(callee, paren, arguments) => {
+                let callee_type = self.expression(callee);
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect::<Vec<Type>>();
+                if callee_type == Type::Nil && optional(callee) {
+                    return Type::Nil;
+                }
+                self.call(&callee_name(callee), callee_type, paren, &arguments)
+            }
+            Expr::Conditional(condition, _, then_branch, else_branch) => {
+                self.expression(condition);
+                let then_branch = self.expression(then_branch);
+                let else_branch = self.expression(else_branch);
+                if then_branch == else_branch {
+                    then_branch
+                } else {
+                    Type::Any
+                }
+            }
+            Expr::Index(object, bracket, index) => {
+                let object = self.expression(object);
+                let index = self.expression(index);
+                let method = match &object {
+                    Type::Instance(class) => self.dispatched(class, "__index__"),
+                    _ => None,
+                };
+                match (method, object) {
+                    (Some(method), _) => self.call("__index__", method, bracket, &[index]),
+                    (None, Type::Any) => Type::Any,
+                    (None, Type::Nil) if optional(expr) => Type::Nil,
+                    (None, object) => {
+                        self.error(
+                            bracket,
+                            format!(
+                                "Can only index instances with an '__index__' method, got {}",
+                                object
+                            ),
+                        );
+                        Type::Any
+                    }
+                }
+            }
+            Expr::Get(object, name) | Expr::OptionalGet(object, name) => {
+                let object = self.expression(object);
+                match object {
+                    Type::Instance(class) => self
+                        .field(&class, &name.lexeme)
+                        .or_else(|| self.dispatched(&class, &name.lexeme))
+                        .unwrap_or(Type::Any),
+                    Type::Class(class) => self.static_member(&class, &name.lexeme),
+                    Type::Any => Type::Any,
+                    Type::Nil if optional(expr) => Type::Nil,
+                    object => {
+                        self.error(
+                            name,
+                            format!("Only instances have properties, got {}", object),
+                        );
+                        Type::Any
+                    }
+                }
+            }
+            Expr::Set(object, name, value) => {
+                let object = self.expression(object);
+                let value = self.expression(value);
+                match object {
+                    Type::Instance(class) => {
+                        if let Some(field) = self.field(&class, &name.lexeme) {
+                            if !accepts(&field, &value, &self.classes) {
+                                self.error(
+                                    name,
+                                    format!(
+                                        "Can't assign {} to field '{}' of type {}",
+                                        value, name.lexeme, field
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                    Type::Class(_) | Type::Any => (),
+                    object => {
+                        self.error(name, format!("Only instances have fields, got {}", object))
+                    }
+                }
+                value
+            }
+            Expr::CompoundAssign(name, operator, value) => {
+                let value = self.expression(value);
+                let declared = self.lookup(&name.lexeme);
+                let reported = self.errors.len();
+                let updated = self.binary(&operator.compound_operator(), declared.clone(), value);
+                // a bad operand has already been reported, don't report its result too
+                if self.errors.len() == reported && !accepts(&declared, &updated, &self.classes) {
+                    self.error(
+                        name,
+                        format!(
+                            "Can't assign {} to '{}' of type {}",
+                            updated, name.lexeme, declared
+                        ),
+                    );
+                }
+                updated
+            }
+            Expr::CompoundSet(object, name, operator, value) => {
+                let object = self.expression(object);
+                let value = self.expression(value);
+                let field = match object {
+                    Type::Instance(class) => self.field(&class, &name.lexeme).unwrap_or(Type::Any),
+                    Type::Class(class) => self.static_member(&class, &name.lexeme),
+                    Type::Any => Type::Any,
+                    object => {
+                        self.error(name, format!("Only instances have fields, got {}", object));
+                        Type::Any
+                    }
+                };
+                let reported = self.errors.len();
+                let updated = self.binary(&operator.compound_operator(), field.clone(), value);
+                if self.errors.len() == reported && !accepts(&field, &updated, &self.classes) {
+                    self.error(
+                        name,
+                        format!(
+                            "Can't assign {} to field '{}' of type {}",
+                            updated, name.lexeme, field
+                        ),
+                    );
+                }
+                updated
+            }
+            Expr::Postfix(update, _) => self.expression(update),
+            Expr::This(_) => match &self.current_class {
+                Some(class) => Type::Instance(class.clone()),
+                None => Type::Any,
+            },
+            Expr::Super(_, method) => {
+                let superclass = self
+                    .current_class
+                    .as_ref()
+                    .and_then(|class| self.classes.get(class))
+                    .and_then(|info| info.superclass.clone());
+                superclass
+                    .and_then(|superclass| self.method(&superclass, &method.lexeme))
+                    .unwrap_or(Type::Any)
+            }
+        }
+    }
+
+    fn binary(&mut self, operator: &Token, left: Type, right: Type) -> Type {
+        if let Some(overloaded) = self.overloaded(operator, &left, &right) {
+            return overloaded;
+        }
+        match operator.token_type {
+            TokenType::EqualEqual | TokenType::BangEqual => Type::Bool,
+            TokenType::Is => {
+                if !matches!(right, Type::Class(_) | Type::Any) {
+                    self.error(
+                        operator,
+                        format!("Right operand of 'is' must be a class, got {}", right),
+                    );
+                }
+                Type::Bool
+            }
+            TokenType::Plus => {
+                let addable = |operand: &Type| matches!(operand, Type::Any | Type::Num | Type::Str);
+                let mismatched = left != right && left != Type::Any && right != Type::Any;
+                if !addable(&left) || !addable(&right) || mismatched {
+                    self.error(
+                        operator,
+                        format!(
+                            "Operands of '+' must be two numbers or two strings, got {} and {}",
+                            left, right
+                        ),
+                    );
+                    return Type::Any;
+                }
+                // whichever side is known decides what comes out
+                if left == Type::Any {
+                    right
+                } else {
+                    left
+                }
+            }
+            _ => {
+                if !is_number(&left) || !is_number(&right) {
+                    self.error(
+                        operator,
+                        format!(
+                            "Operands of '{}' must be numbers, got {} and {}",
+                            operator.lexeme, left, right
+                        ),
+                    );
+                }
+                match operator.token_type {
+                    TokenType::Greater
+                    | TokenType::GreaterEqual
+                    | TokenType::Less
+                    | TokenType::LessEqual => Type::Bool,
+                    _ => Type::Num,
+                }
+            }
+        }
+    }
+
+    // the type an operator gives when an instance operand overloads it
+    fn overloaded(&self, operator: &Token, left: &Type, right: &Type) -> Option<Type> {
+        if let Some(overload) = operator.overload() {
+            let receiver = match (overload.swapped, overload.method, left) {
+                (true, _, _) => right,
+                (false, "__eq__", Type::Instance(_)) => left,
+                // equality goes both ways, so the right operand can answer it
+                (false, "__eq__", _) => right,
+                (false, _, _) => left,
+            };
+            if let Type::Instance(class) = receiver {
+                if let Some(method) = self.dispatched(class, overload.method) {
+                    return Some(match (overload.method, method) {
+                        ("__eq__" | "__lt__", _) => Type::Bool,
+                        (_, Type::Function(Some((_, returns)))) => *returns,
+                        _ => Type::Any,
+                    });
+                }
+            }
+        }
+        match (&operator.token_type, left, right) {
+            // instances without __str__ concatenate the way they print
+            (TokenType::Plus, Type::Str, Type::Instance(_))
+            | (TokenType::Plus, Type::Instance(_), Type::Str) => Some(Type::Str),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, name: &str, callee: Type, paren: &Token, arguments: &[Type]) -> Type {
+        let (parameters, returns) = match callee {
+            Type::Function(Some((parameters, returns))) => (parameters, *returns),
+            Type::Class(class) => {
+                let parameters = match self.method(&class, "init") {
+                    Some(Type::Function(Some((parameters, _)))) => parameters,
+                    Some(_) => return Type::Instance(class),
+                    None => Vec::new(),
+                };
+                (parameters, Type::Instance(class))
+            }
+            Type::Function(None) | Type::Any => return Type::Any,
+            callee => {
+                self.error(
+                    paren,
+                    format!("Can only call functions and classes, got {}", callee),
+                );
+                return Type::Any;
+            }
+        };
+        if parameters.len() != arguments.len() {
+            self.error(
+                paren,
+                format!(
+                    "'{}' expects {} arguments but got {}",
+                    name,
+                    parameters.len(),
+                    arguments.len()
+                ),
+            );
+            return returns;
+        }
+        for (index, (parameter, argument)) in parameters.iter().zip(arguments).enumerate() {
+            if !accepts(parameter, argument, &self.classes) {
+                self.error(
+                    paren,
+                    format!(
+                        "Argument {} of '{}' must be {}, got {}",
+                        index + 1,
+                        name,
+                        parameter,
+                        argument
+                    ),
+                );
+            }
+        }
+        returns
+    }
+
+    // looks through the class and its superclasses
+    fn field(&self, class: &str, name: &str) -> Option<Type> {
+        let info = self.classes.get(class)?;
+        match info.fields.get(name) {
+            Some(field) => Some(field.clone()),
+            None => self.field(info.superclass.as_ref()?, name),
+        }
+    }
+
+    fn method(&self, class: &str, name: &str) -> Option<Type> {
+        let info = self.classes.get(class)?;
+        match info.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.method(info.superclass.as_ref()?, name),
+        }
+    }
+
+    // the method an instance of the class runs, which may be a subclass's override
+    // with other parameters, so only methods nothing overrides have a known signature
+    fn dispatched(&self, class: &str, name: &str) -> Option<Type> {
+        let method = self.method(class, name)?;
+        let overridden = self.declared.iter().any(|(subclass, declared)| {
+            let overrides = declared.methods.contains(name)
+                || declared.traits.iter().any(|mixed| {
+                    self.declared
+                        .get(mixed)
+                        .is_some_and(|mixed| mixed.methods.contains(name))
+                });
+            subclass != class && overrides && self.inherits(subclass, class)
+        });
+        if overridden {
+            Some(Type::Function(None))
+        } else {
+            Some(method)
+        }
+    }
+
+    // whether class is ancestor or one of its subclasses, as declared anywhere
+    fn inherits(&self, class: &str, ancestor: &str) -> bool {
+        let mut class = class;
+        // a class can't be its own ancestor, so no chain is longer than this
+        for _ in 0..=self.declared.len() {
+            if class == ancestor {
+                return true;
+            }
+            match self
+                .declared
+                .get(class)
+                .and_then(|declared| declared.superclass.as_deref())
+            {
+                Some(superclass) => class = superclass,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    // anything can be stored on a class, so only static methods have a known type
+    fn static_member(&self, class: &str, name: &str) -> Type {
+        let info = match self.classes.get(class) {
+            Some(info) => info,
+            None => return Type::Any,
+        };
+        match (info.statics.get(name), &info.superclass) {
+            (Some(method), _) => method.clone(),
+            (None, Some(superclass)) => self.static_member(superclass, name),
+            (None, None) => Type::Any,
+        }
+    }
+}
+
+// whether a chain of calls, property reads and indexes has a "?." in it, which
+// gives nil for the whole chain when it meets nil
+fn optional(expr: &Expr) -> bool {
+    match expr {
+        Expr::OptionalGet(..) => true,
+        Expr::Get(object, _) | Expr::Call(object, _, _) | Expr::Index(object, _, _) => {
+            optional(object)
+        }
+        _ => false,
+    }
+}
+
+// the instance methods among a class's or trait's members
+fn method_names(members: &[Stmt]) -> HashSet<String> {
+    members
+        .iter()
+        .filter_map(|member| match member {
+            Stmt::FunctionDeclaration(name, _, _, signature)
+                if signature.kind == MethodKind::Method =>
+            {
+                Some(name.lexeme.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn function(parameters: Vec<Type>, returns: Type) -> Type {
+    Type::Function(Some((parameters, Box::from(returns))))
+}
+
+fn is_number(operand: &Type) -> bool {
+    matches!(operand, Type::Any | Type::Num)
+}
+
+// whether a value of type actual can go where expected is wanted; nil fits
+// anywhere, as variables and fields start out nil
+fn accepts(expected: &Type, actual: &Type, classes: &HashMap<String, ClassInfo>) -> bool {
+    match (expected, actual) {
+        (Type::Any, _) | (_, Type::Any) | (_, Type::Nil) => true,
+        (Type::Function(_), Type::Function(_)) => true,
+        (Type::Instance(expected), Type::Instance(actual)) => {
+            is_subclass(actual, expected, classes)
+        }
+        _ => expected == actual,
+    }
+}
+
+fn is_subclass(class: &str, ancestor: &str, classes: &HashMap<String, ClassInfo>) -> bool {
+    if class == ancestor {
+        return true;
+    }
+    match classes.get(class).and_then(|info| info.superclass.as_ref()) {
+        Some(superclass) => is_subclass(superclass, ancestor, classes),
+        None => false,
+    }
+}
+
+// how a call's callee is named in messages
+fn callee_name(callee: &Expr) -> String {
+    match callee {
+        Expr::Variable(name) | Expr::Get(_, name) | Expr::Super(_, name) => name.lexeme.clone(),
+        _ => "function".to_string(),
+    }
+}
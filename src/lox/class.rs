@@ -1,65 +1,209 @@
-use std::collections::HashMap;
 use std::fmt::Display;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::{callable::Callable, instance::Instance, value::Value};
 
+// what a class declares, split up by how each kind of member is looked up
+#[derive(Default, PartialEq, Debug)]
+pub struct Members {
+    // declared fields in order, each with a function giving its starting value
+    pub fields: Vec<(String, Value)>,
+    pub methods: HashMap<String, Value>,
+    pub getters: HashMap<String, Value>,
+    pub setters: HashMap<String, Value>,
+    pub statics: HashMap<String, Value>,
+}
+
+impl Members {
+    // copies in the members of each trait the class doesn't declare itself; two
+    // traits giving the same member is an error unless the class declares its own
+    pub fn mix_in(&mut self, class: &str, traits: &[Trait]) -> Result<(), String> {
+        let mut sources: HashMap<String, String> = HashMap::new();
+        // trait fields start out before the class's own, which can build on them
+        let mut fields = Vec::new();
+        for mixed in traits {
+            for (name, initializer) in &mixed.members.fields {
+                if let Some(first) = sources.get(name) {
+                    return Err(conflict(first, mixed, name, class));
+                }
+                if !self.fields.iter().any(|(own, _)| own == name) {
+                    sources.insert(name.clone(), mixed.name.clone());
+                    fields.push((name.clone(), initializer.clone()));
+                }
+            }
+            let kinds = [
+                (&mut self.methods, &mixed.members.methods),
+                (&mut self.getters, &mixed.members.getters),
+                (&mut self.setters, &mixed.members.setters),
+                (&mut self.statics, &mixed.members.statics),
+            ];
+            for (own, from) in kinds {
+                for (name, member) in from {
+                    if let Some(first) = sources.get(name) {
+                        return Err(conflict(first, mixed, name, class));
+                    }
+                    if !own.contains_key(name) {
+                        sources.insert(name.clone(), mixed.name.clone());
+                        own.insert(name.clone(), member.clone());
+                    }
+                }
+            }
+        }
+        self.fields.splice(0..0, fields);
+        Ok(())
+    }
+}
+
+fn conflict(first: &str, second: &Trait, name: &str, class: &str) -> String {
+    format!(
+        "Traits {} and {} both define '{}', {} must declare its own",
+        first, second.name, name, class
+    )
+}
+
+// fields and methods shared by unrelated classes, mixed into each at its declaration
+#[derive(Clone, PartialEq, Debug)]
+pub struct Trait {
+    name: String,
+    members: Rc<Members>,
+}
+
+impl Trait {
+    pub fn new(name: String, members: Members) -> Self {
+        Self {
+            name,
+            members: Rc::new(members),
+        }
+    }
+}
+
+impl Display for Trait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Class {
     name: String,
     superclass: Option<Box<Class>>,
-    methods: HashMap<String, Value>,
+    // shared by every copy of the class, its statics are kept apart below as they can change
+    members: Rc<Members>,
+    // static methods and anything assigned on the class, shared by every copy of it
+    statics: Rc<RefCell<HashMap<String, Value>>>,
 }
 
 impl Class {
-    pub fn new(
-        name: String,
-        superclass: Option<Box<Class>>,
-        methods: HashMap<String, Value>,
-    ) -> Self {
+    pub fn new(name: String, superclass: Option<Box<Class>>, mut members: Members) -> Self {
+        let statics = std::mem::take(&mut members.statics);
         Self {
             name,
             superclass,
-            methods,
+            members: Rc::new(members),
+            statics: Rc::new(RefCell::new(statics)),
         }
     }
 
     pub fn find_method(&self, name: &String) -> Option<&Value> {
-        match self.methods.get(name) {
-            Some(_) => self.methods.get(name),
+        match self.members.methods.get(name) {
+            Some(_) => self.members.methods.get(name),
             None => match self.superclass {
                 Some(_) => self.superclass.as_ref().unwrap().find_method(name),
                 None => None,
             },
         }
     }
+
+    pub fn superclass(&self) -> Option<&Class> {
+        self.superclass.as_deref()
+    }
+
+    // classes are the same when they share statics, which only copies of one class do
+    pub fn is_subclass_of(&self, ancestor: &Class) -> bool {
+        Rc::ptr_eq(&self.statics, &ancestor.statics)
+            || self
+                .superclass()
+                .is_some_and(|superclass| superclass.is_subclass_of(ancestor))
+    }
+
+    // superclass fields first, so a subclass's defaults win
+    fn initialize_fields(
+        &self,
+        instance: &Instance,
+        interpreter: &mut super::interpreter::Interpreter,
+    ) -> Result<(), String> {
+        if let Some(superclass) = self.superclass() {
+            superclass.initialize_fields(instance, interpreter)?;
+        }
+        for (name, initializer) in &self.members.fields {
+            let value = initializer.bind(instance)?.call(interpreter, Vec::new())?;
+            instance.set(name.clone(), value);
+        }
+        Ok(())
+    }
+
+    pub fn find_getter(&self, name: &str) -> Option<&Value> {
+        match self.members.getters.get(name) {
+            Some(getter) => Some(getter),
+            None => self.superclass.as_ref()?.find_getter(name),
+        }
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<&Value> {
+        match self.members.setters.get(name) {
+            Some(setter) => Some(setter),
+            None => self.superclass.as_ref()?.find_setter(name),
+        }
+    }
+
+    // statics are inherited too, so a subclass can call its superclass's
+    pub fn get(&self, name: &str) -> Result<Value, String> {
+        let value = self.statics.borrow().get(name).cloned();
+        match (value, &self.superclass) {
+            (Some(value), _) => Ok(value),
+            (None, Some(superclass)) => superclass.get(name),
+            (None, None) => Err(format!("Undefined property '{}'", name)),
+        }
+    }
+
+    pub fn set(&self, name: String, value: Value) {
+        self.statics.borrow_mut().insert(name, value);
+    }
 }
 
 impl Display for Class {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(class {} < {:?})", self.name, self.superclass)
+        write!(f, "{}", self.name)
     }
 }
 
 impl Callable for Class {
     fn arity(&self) -> usize {
         match self.find_method(&"init".to_string()) {
-            Some(initializer_value) => match initializer_value {
-                Value::Function(function) => function.arity(),
-                _ => 0,
-            },
-            None => 0,
+            Some(Value::Function(function)) => function.arity(),
+            _ => 0,
         }
     }
 
     fn call(
         &self,
-        _interpreter: &mut super::interpreter::Interpreter,
-        _arguments: Vec<super::value::Value>,
+        interpreter: &mut super::interpreter::Interpreter,
+        arguments: Vec<super::value::Value>,
     ) -> Result<super::value::Value, String> {
+        if arguments.len() != self.arity() {
+            return Err(format!(
+                "Expected {} arguments but got {} arguments",
+                self.arity(),
+                arguments.len()
+            ));
+        }
         let instance = Instance::new(self.clone());
+        self.initialize_fields(&instance, interpreter)?;
         let initializer = self.find_method(&"init".to_string());
         if let Some(initializer_value) = initializer {
-            initializer_value.bind(&instance)?;
+            initializer_value
+                .bind(&instance)?
+                .call(interpreter, arguments)?;
         }
         Ok(Value::Instance(instance))
     }
@@ -0,0 +1,280 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    fs,
+    process::exit,
+    rc::Rc,
+};
+
+use super::{
+    expr::Expr,
+    interpreter::{Hook, Interpreter},
+    stmt::Stmt,
+    token::Token,
+    Lox,
+};
+
+const REPORT_FILE: &str = "lcov.info";
+
+// what could have run, found by walking the tree before running it
+#[derive(Default)]
+struct Coverable {
+    lines: BTreeSet<i32>,
+    // keyword or operator position of every if, loop and logical operator
+    branches: BTreeSet<(i32, usize)>,
+    // "name:line", matching the interpreter's frames
+    functions: BTreeSet<(i32, String)>,
+}
+
+// what did run
+#[derive(Default)]
+struct Coverage {
+    lines: HashMap<i32, usize>,
+    branches: HashMap<(i32, usize), [usize; 2]>,
+    functions: HashMap<String, usize>,
+}
+
+impl Lox {
+    // runs the file and writes which lines, branches and functions ran to lcov.info
+    pub fn coverage_file(&mut self, file_name: &str) {
+        let (_, stmts) = self.load_file(file_name);
+
+        let mut coverable = Coverable::default();
+        coverable.statements(&stmts);
+        let coverage = Rc::new(RefCell::new(Coverage::default()));
+        self.interpreter.set_hook(Box::new(coverage.clone()));
+        let result = self.interpreter.interpret(stmts);
+
+        let coverage = coverage.borrow();
+        let (report, summary) = coverage.lcov(file_name, &coverable);
+        match fs::write(REPORT_FILE, report) {
+            Ok(()) => eprintln!("{}\nCoverage written to {}", summary, REPORT_FILE),
+            Err(err) => eprintln!("Could not write '{}': {}", REPORT_FILE, err),
+        }
+
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            exit(70);
+        }
+    }
+}
+
+impl Coverable {
+    fn statements(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        // blocks never reach the interpreter's hook, their statements do
+        if !matches!(stmt, Stmt::Block(_, _)) {
+            self.lines.insert(stmt.line());
+        }
+        match stmt {
+            Stmt::Block(_, stmts) => self.statements(stmts),
+            Stmt::Expression(_, expr)
+            | Stmt::Print(_, expr)
+            | Stmt::Return(_, expr)
+            | Stmt::Yield(_, expr) => self.expression(expr),
+            Stmt::Break(_) | Stmt::Continue(_) => (),
+            Stmt::VariableDeclaration(_, _, initializer) => self.expression(initializer),
+            Stmt::If(keyword, condition, then_branch, else_branch) => {
+                self.branch(keyword);
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = &**else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While(keyword, condition, body) => {
+                self.branch(keyword);
+                self.expression(condition);
+                self.statement(body);
+            }
+            Stmt::ForIn(keyword, _, iterable, body) => {
+                self.branch(keyword);
+                self.expression(iterable);
+                self.statement(body);
+            }
+            Stmt::FunctionDeclaration(_, _, _, _) => self.function(stmt),
+            Stmt::ClassDeclaration(_, superclass, traits, methods) => {
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                for mixed in traits {
+                    self.expression(mixed);
+                }
+                // methods are bound, not executed, so only their bodies count
+                for method in methods {
+                    self.function(method);
+                }
+            }
+            Stmt::TraitDeclaration(_, methods) => {
+                for method in methods {
+                    self.function(method);
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Stmt) {
+        if let Stmt::FunctionDeclaration(name, _, body, _) = function {
+            self.functions
+                .insert((name.line, format!("{}:{}", name.lexeme, name.line)));
+            self.statements(body);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Logical(left, operator, right) => {
+                self.branch(operator);
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Binary(left, _, right) | Expr::Index(left, _, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Assign(_, inner)
+            | Expr::CompoundAssign(_, _, inner)
+            | Expr::Postfix(inner, _)
+            | Expr::Grouping(inner)
+            | Expr::Urnary(_, inner)
+            | Expr::Await(_, inner)
+            | Expr::Spawn(_, inner)
+            | Expr::Get(inner, _)
+            | Expr::OptionalGet(inner, _) => self.expression(inner),
+            Expr::Conditional(condition, question, then_branch, else_branch) => {
+                self.branch(question);
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expr::Set(object, _, value) | Expr::CompoundSet(object, _, _, value) => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::Call(callee, _, arguments) => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expr::StringLiteral(_)
+            | Expr::NumberLiteral(..)
+            | Expr::IntLiteral(..)
+            | Expr::NilLiteral
+            | Expr::TrueLiteral
+            | Expr::FalseLiteral
+            | Expr::Variable(_)
+            | Expr::This(_)
+            | Expr::Super(_, _) => (),
+        }
+    }
+
+    fn branch(&mut self, token: &Token) {
+        self.branches.insert((token.line, token.column));
+    }
+}
+
+impl Coverage {
+    // the lcov tracefile, and a short summary for people
+    fn lcov(&self, file_name: &str, coverable: &Coverable) -> (String, String) {
+        let path = fs::canonicalize(file_name)
+            .map_or(file_name.to_string(), |path| path.display().to_string());
+        let mut report = format!("TN:\nSF:{}\n", path);
+
+        for (line, function) in &coverable.functions {
+            report.push_str(&format!("FN:{},{}\n", line, function));
+        }
+        let mut functions_hit = 0;
+        for (_, function) in &coverable.functions {
+            let calls = self.functions.get(function).copied().unwrap_or(0);
+            if calls > 0 {
+                functions_hit += 1;
+            }
+            report.push_str(&format!("FNDA:{},{}\n", calls, function));
+        }
+        report.push_str(&format!(
+            "FNF:{}\nFNH:{}\n",
+            coverable.functions.len(),
+            functions_hit
+        ));
+
+        let mut branches_hit = 0;
+        for (block, position) in coverable.branches.iter().enumerate() {
+            // "-" marks branches whose condition never ran at all
+            let taken = self.branches.get(position);
+            for arm in 0..2 {
+                let count = match taken {
+                    Some(counts) => {
+                        if counts[arm] > 0 {
+                            branches_hit += 1;
+                        }
+                        counts[arm].to_string()
+                    }
+                    None => "-".to_string(),
+                };
+                report.push_str(&format!(
+                    "BRDA:{},{},{},{}\n",
+                    position.0, block, arm, count
+                ));
+            }
+        }
+        let branches = coverable.branches.len() * 2;
+        report.push_str(&format!("BRF:{}\nBRH:{}\n", branches, branches_hit));
+
+        let mut lines_hit = 0;
+        for line in &coverable.lines {
+            let hits = self.lines.get(line).copied().unwrap_or(0);
+            if hits > 0 {
+                lines_hit += 1;
+            }
+            report.push_str(&format!("DA:{},{}\n", line, hits));
+        }
+        report.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            coverable.lines.len(),
+            lines_hit
+        ));
+
+        let summary = format!(
+            "Lines: {}\nBranches: {}\nFunctions: {}",
+            percentage(lines_hit, coverable.lines.len()),
+            percentage(branches_hit, branches),
+            percentage(functions_hit, coverable.functions.len())
+        );
+        (report, summary)
+    }
+}
+
+fn percentage(hit: usize, total: usize) -> String {
+    if total == 0 {
+        return "0/0".to_string();
+    }
+    format!(
+        "{}/{} ({:.1}%)",
+        hit,
+        total,
+        hit as f64 * 100.0 / total as f64
+    )
+}
+
+impl Hook for Coverage {
+    fn before_statement(&mut self, _interpreter: &mut Interpreter, stmt: &Stmt) {
+        *self.lines.entry(stmt.line()).or_default() += 1;
+    }
+
+    fn enter_call(&mut self, interpreter: &mut Interpreter) {
+        if let Some(frame) = interpreter.frames.last() {
+            let function = format!("{}:{}", frame.name, frame.line);
+            *self.functions.entry(function).or_default() += 1;
+        }
+    }
+
+    fn branch(&mut self, _interpreter: &mut Interpreter, token: &Token, arm: usize) {
+        self.branches.entry((token.line, token.column)).or_default()[arm] += 1;
+    }
+}
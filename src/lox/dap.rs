@@ -0,0 +1,482 @@
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, BufRead, Write},
+    process::exit,
+    rc::Rc,
+};
+
+use serde_json::{json, Value as Json};
+
+use super::{
+    checker::Checker,
+    debugger::{evaluate_in_frame, Step, Stepping},
+    environment::Environment,
+    instance::Instance,
+    interpreter::{Hook, Interpreter},
+    lsp::{read_message, write_message},
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    stmt::Stmt,
+    value::Value,
+};
+
+// the interpreter is single threaded, so there is only ever one thread to report
+const THREAD_ID: i64 = 1;
+
+// something the client can expand in the variables view
+enum Container {
+    Scope(Rc<RefCell<Environment>>),
+    Instance(Instance),
+}
+
+// what a request asks the paused program to do next
+enum Action {
+    Stay,
+    Resume(Step),
+    Disconnect,
+}
+
+// a debug adapter speaking the Debug Adapter Protocol, the client launches a program,
+// sets breakpoints, and then drives the interpreter while it is paused
+pub struct Adapter<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    seq: i64,
+    program: Option<String>,
+    stop_on_entry: bool,
+    stepping: Stepping,
+    stopped_before: bool,
+    // text printed by the program since the last output event
+    output: Rc<RefCell<Vec<u8>>>,
+    // handed out as variablesReference - 1, only valid until the program resumes
+    containers: Vec<Container>,
+}
+
+// the interpreter owns its hook, so the adapter is shared with it
+struct Session<R: BufRead, W: Write>(Rc<RefCell<Adapter<R, W>>>);
+
+// collects print output so it can be sent as output events
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<R: BufRead + 'static, W: Write + 'static> Adapter<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            seq: 0,
+            program: None,
+            stop_on_entry: false,
+            stepping: Stepping::new(Step::Continue),
+            stopped_before: false,
+            output: Rc::new(RefCell::new(Vec::new())),
+            containers: Vec::new(),
+        }
+    }
+
+    // handles requests until the client disconnects, running the program
+    // once the client is done configuring it
+    pub fn run(self) -> io::Result<()> {
+        let adapter = Rc::new(RefCell::new(self));
+        loop {
+            let request = match adapter.borrow_mut().read_request()? {
+                Some(request) => request,
+                None => return Ok(()),
+            };
+            let command = request["command"].as_str().unwrap_or("").to_string();
+            let arguments = &request["arguments"];
+            let mut adapter_ref = adapter.borrow_mut();
+            match command.as_str() {
+                "initialize" => {
+                    adapter_ref.respond(
+                        &request,
+                        json!({
+                            "supportsConfigurationDoneRequest": true,
+                            "supportsEvaluateForHovers": true,
+                        }),
+                    )?;
+                    adapter_ref.event("initialized", json!({}))?;
+                }
+                "launch" => match arguments["program"].as_str() {
+                    Some(program) => {
+                        adapter_ref.program = Some(program.to_string());
+                        adapter_ref.stop_on_entry =
+                            arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                        adapter_ref.respond(&request, json!({}))?;
+                    }
+                    None => adapter_ref.fail(&request, "launch needs a program")?,
+                },
+                "configurationDone" => {
+                    adapter_ref.respond(&request, json!({}))?;
+                    drop(adapter_ref);
+                    Self::launch(&adapter)?;
+                }
+                _ => match adapter_ref.handle(&request, None)? {
+                    Action::Disconnect => return Ok(()),
+                    Action::Stay | Action::Resume(_) => (),
+                },
+            }
+        }
+    }
+
+    // runs the program to completion, the hook takes over whenever it pauses
+    fn launch(adapter: &Rc<RefCell<Self>>) -> io::Result<()> {
+        let program = adapter.borrow().program.clone().unwrap_or_default();
+        let mut interpreter = Interpreter::new();
+        let result = match fs::read_to_string(&program) {
+            Ok(source) => Scanner::new(&source)
+                .scan_tokens()
+                .and_then(|tokens| Parser::new(tokens).parse())
+                .and_then(|stmts| {
+                    Resolver::new(&interpreter).resolve(&stmts)?;
+                    Checker::new().check(&stmts)?;
+                    Ok(stmts)
+                }),
+            Err(err) => Err(format!("Could not read '{}': {}", program, err)),
+        };
+        let exit_code = match result {
+            Ok(stmts) => {
+                {
+                    let mut adapter = adapter.borrow_mut();
+                    let step = if adapter.stop_on_entry {
+                        Step::In
+                    } else {
+                        Step::Continue
+                    };
+                    adapter.stepping.resume(step);
+                    interpreter.set_output(Box::new(Output(adapter.output.clone())));
+                }
+                interpreter.set_hook(Box::new(Session(adapter.clone())));
+                let result = interpreter.interpret(stmts);
+                adapter.borrow_mut().flush_output()?;
+                match result {
+                    Ok(()) => 0,
+                    Err(err) => {
+                        adapter
+                            .borrow_mut()
+                            .print(&format!("{}\n", err), "stderr")?;
+                        70
+                    }
+                }
+            }
+            Err(err) => {
+                adapter
+                    .borrow_mut()
+                    .print(&format!("{}\n", err), "stderr")?;
+                65
+            }
+        };
+        let mut adapter = adapter.borrow_mut();
+        adapter.event("exited", json!({ "exitCode": exit_code }))?;
+        adapter.event("terminated", json!({}))
+    }
+
+    // requests that make sense both while paused and while nothing is running,
+    // the interpreter is only there while paused
+    fn handle(
+        &mut self,
+        request: &Json,
+        interpreter: Option<&mut Interpreter>,
+    ) -> io::Result<Action> {
+        let command = request["command"].as_str().unwrap_or("");
+        let arguments = &request["arguments"];
+        match (command, interpreter) {
+            ("setBreakpoints", _) => {
+                let lines = arguments["breakpoints"]
+                    .as_array()
+                    .map(|breakpoints| {
+                        breakpoints
+                            .iter()
+                            .filter_map(|breakpoint| breakpoint["line"].as_i64())
+                            .map(|line| line as i32)
+                            .collect::<Vec<i32>>()
+                    })
+                    .unwrap_or_default();
+                // breakpoints in other files can never be hit
+                let path = arguments["source"]["path"].as_str();
+                let verified = path.is_none() || path == self.program.as_deref();
+                if verified {
+                    self.stepping.breakpoints = lines.iter().copied().collect();
+                }
+                let breakpoints = lines
+                    .iter()
+                    .map(|line| json!({ "verified": verified, "line": line }))
+                    .collect::<Vec<Json>>();
+                self.respond(request, json!({ "breakpoints": breakpoints }))?;
+            }
+            ("threads", _) => {
+                self.respond(
+                    request,
+                    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+                )?;
+            }
+            ("disconnect", _) => {
+                self.respond(request, json!({}))?;
+                return Ok(Action::Disconnect);
+            }
+            ("continue", Some(_)) => {
+                self.respond(request, json!({ "allThreadsContinued": true }))?;
+                return Ok(Action::Resume(Step::Continue));
+            }
+            ("next", Some(interpreter)) => {
+                self.respond(request, json!({}))?;
+                return Ok(Action::Resume(Step::Over(interpreter.frames.len())));
+            }
+            ("stepIn", Some(_)) => {
+                self.respond(request, json!({}))?;
+                return Ok(Action::Resume(Step::In));
+            }
+            ("stepOut", Some(interpreter)) => {
+                self.respond(request, json!({}))?;
+                return Ok(Action::Resume(Step::Out(interpreter.frames.len())));
+            }
+            ("stackTrace", Some(interpreter)) => {
+                let program = self.program.clone().unwrap_or_default();
+                let frames = interpreter
+                    .frames
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(id, frame)| {
+                        json!({
+                            "id": id,
+                            "name": frame.name,
+                            "line": frame.line,
+                            "column": 1,
+                            "source": { "path": program },
+                        })
+                    })
+                    .collect::<Vec<Json>>();
+                let total = frames.len();
+                self.respond(
+                    request,
+                    json!({ "stackFrames": frames, "totalFrames": total }),
+                )?;
+            }
+            ("scopes", Some(interpreter)) => {
+                let frame_id = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+                let mut scopes = Vec::new();
+                if let Some(frame) = interpreter.frames.get(frame_id) {
+                    let mut environment = frame.environment.clone();
+                    while !Rc::ptr_eq(&environment, &interpreter.globals) {
+                        let name = if scopes.is_empty() {
+                            "Locals".to_string()
+                        } else {
+                            format!("Enclosing {}", scopes.len())
+                        };
+                        let reference = self.contain(Container::Scope(environment.clone()));
+                        scopes.push(json!({
+                            "name": name,
+                            "variablesReference": reference,
+                            "expensive": false,
+                        }));
+                        let enclosing = environment.borrow().enclosing();
+                        match enclosing {
+                            Some(enclosing) => environment = enclosing,
+                            None => break,
+                        }
+                    }
+                }
+                let reference = self.contain(Container::Scope(interpreter.globals.clone()));
+                scopes.push(json!({
+                    "name": "Globals",
+                    "variablesReference": reference,
+                    "expensive": false,
+                }));
+                self.respond(request, json!({ "scopes": scopes }))?;
+            }
+            ("variables", Some(_)) => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+                let values = match reference
+                    .checked_sub(1)
+                    .and_then(|index| self.containers.get(index))
+                {
+                    Some(Container::Scope(environment)) => environment.borrow().values().clone(),
+                    Some(Container::Instance(instance)) => instance.fields(),
+                    None => Default::default(),
+                };
+                let mut names = values.keys().cloned().collect::<Vec<String>>();
+                names.sort();
+                let variables = names
+                    .iter()
+                    .map(|name| self.variable(name, &values[name]))
+                    .collect::<Vec<Json>>();
+                self.respond(request, json!({ "variables": variables }))?;
+            }
+            ("evaluate", Some(interpreter)) => {
+                let expression = arguments["expression"].as_str().unwrap_or("");
+                // without a frame, expressions are evaluated in the global scope
+                let environment = match arguments["frameId"].as_u64() {
+                    Some(frame_id) => interpreter
+                        .frames
+                        .get(frame_id as usize)
+                        .map(|frame| frame.environment.clone()),
+                    None => Some(interpreter.globals.clone()),
+                };
+                let result = match environment {
+                    Some(environment) => {
+                        evaluate_in_frame(interpreter, Some(environment), expression)
+                    }
+                    None => Err("Unknown frame".to_string()),
+                };
+                self.flush_output()?;
+                match result {
+                    Ok(value) => {
+                        let body = self.variable(expression, &value);
+                        self.respond(
+                            request,
+                            json!({
+                                "result": body["value"],
+                                "type": body["type"],
+                                "variablesReference": body["variablesReference"],
+                            }),
+                        )?;
+                    }
+                    Err(err) => self.fail(request, &err)?,
+                }
+            }
+            (_, Some(_)) => self.fail(request, &format!("Unsupported request {}", command))?,
+            (_, None) => self.fail(request, &format!("{} needs a paused program", command))?,
+        }
+        Ok(Action::Stay)
+    }
+
+    fn variable(&mut self, name: &str, value: &Value) -> Json {
+        let reference = match value {
+            Value::Instance(instance) => self.contain(Container::Instance(instance.clone())),
+            _ => 0,
+        };
+        json!({
+            "name": name,
+            "value": value.to_string(),
+            "type": value.type_name(),
+            "variablesReference": reference,
+        })
+    }
+
+    fn contain(&mut self, container: Container) -> usize {
+        self.containers.push(container);
+        self.containers.len()
+    }
+
+    fn read_request(&mut self) -> io::Result<Option<Json>> {
+        loop {
+            let message = match read_message(&mut self.reader)? {
+                Some(message) => message,
+                None => return Ok(None),
+            };
+            match serde_json::from_str::<Json>(&message) {
+                Ok(request) if request["type"] == "request" => return Ok(Some(request)),
+                // responses to reverse requests and garbage are both ignored
+                _ => continue,
+            }
+        }
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn print(&mut self, text: &str, category: &str) -> io::Result<()> {
+        self.event("output", json!({ "category": category, "output": text }))
+    }
+
+    fn flush_output(&mut self) -> io::Result<()> {
+        let text = String::from_utf8_lossy(&self.output.borrow()).to_string();
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.output.borrow_mut().clear();
+        self.print(&text, "stdout")
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.writer, &message)
+    }
+
+    // waits for requests until one of them resumes the program
+    fn pause(&mut self, interpreter: &mut Interpreter, line: i32) -> io::Result<()> {
+        let reason = if self.stepping.at_breakpoint(line) {
+            "breakpoint"
+        } else if !self.stopped_before && self.stop_on_entry {
+            "entry"
+        } else {
+            "step"
+        };
+        self.stopped_before = true;
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+        loop {
+            let request = match self.read_request()? {
+                Some(request) => request,
+                // the client went away, so there is nobody to pause for
+                None => {
+                    self.stepping.resume(Step::Continue);
+                    self.stepping.breakpoints.clear();
+                    break;
+                }
+            };
+            match self.handle(&request, Some(interpreter))? {
+                Action::Stay => (),
+                Action::Resume(step) => {
+                    self.stepping.resume(step);
+                    break;
+                }
+                Action::Disconnect => exit(0),
+            }
+        }
+        self.containers.clear();
+        Ok(())
+    }
+}
+
+impl<R: BufRead + 'static, W: Write + 'static> Hook for Session<R, W> {
+    fn before_statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) {
+        let mut adapter = self.0.borrow_mut();
+        let line = stmt.line();
+        // a failed write means the client is gone, which the next read will notice
+        let _ = adapter.flush_output();
+        if adapter
+            .stepping
+            .should_pause(line, interpreter.frames.len())
+        {
+            let _ = adapter.pause(interpreter, line);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    io::{self, BufRead, Write},
+    process::exit,
+    rc::Rc,
+};
+
+use super::{
+    environment::Environment,
+    expr::Expr,
+    interpreter::{Hook, Interpreter},
+    parser::Parser,
+    scanner::Scanner,
+    stmt::Stmt,
+    value::Value,
+    Lox,
+};
+
+const PROMPT: &str = "(debug) ";
+
+const HELP: &str = "\
+break <line>     pause when <line> is reached (b)
+delete <line>    remove the breakpoint on <line>
+breakpoints      list the breakpoints
+continue         run until the next breakpoint (c)
+step             run to the next statement, entering calls (s)
+next             run to the next statement in this function or its caller (n)
+finish           run until the current function returns (f)
+locals           show the variables in scope, innermost first (l)
+globals          show the global variables
+print <expr>     evaluate <expr> in the paused frame (p)
+backtrace        show the active calls, innermost first (bt)
+help             show this message (h)
+quit             stop the program (q)";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Step {
+    Continue,
+    In,
+    // frame depth the step started at
+    Over(usize),
+    Out(usize),
+}
+
+// decides where execution pauses, shared by every debugger front end
+pub struct Stepping {
+    pub breakpoints: BTreeSet<i32>,
+    step: Step,
+    // line and depth of the last statement, breakpoints only fire when entering a line
+    previous: Option<(i32, usize)>,
+}
+
+impl Stepping {
+    pub fn new(step: Step) -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            step,
+            previous: None,
+        }
+    }
+
+    pub fn resume(&mut self, step: Step) {
+        self.step = step;
+    }
+
+    pub fn should_pause(&mut self, line: i32, depth: usize) -> bool {
+        let entered_line = self.previous != Some((line, depth));
+        self.previous = Some((line, depth));
+        let stepped = match self.step {
+            Step::Continue => false,
+            Step::In => true,
+            Step::Over(from) => depth <= from,
+            Step::Out(from) => depth < from,
+        };
+        stepped || (entered_line && self.breakpoints.contains(&line))
+    }
+
+    // true when the last pause was caused by a breakpoint rather than a step
+    pub fn at_breakpoint(&self, line: i32) -> bool {
+        self.step == Step::Continue && self.breakpoints.contains(&line)
+    }
+}
+
+// a command line debugger reading commands from stdin whenever the program pauses
+struct Debugger {
+    source_lines: Vec<String>,
+    stepping: Stepping,
+}
+
+impl Lox {
+    // runs the file under the debugger, pausing before the first statement
+    pub fn debug_file(&mut self, file_name: &str) {
+        let (source, stmts) = self.load_file(file_name);
+
+        println!("Debugging {}, type 'help' for commands", file_name);
+        self.interpreter.set_hook(Box::new(Debugger {
+            source_lines: source.lines().map(String::from).collect(),
+            stepping: Stepping::new(Step::In),
+        }));
+        if let Err(err) = self.interpreter.interpret(stmts) {
+            eprintln!("{}", err);
+            exit(70);
+        }
+        println!("Program finished");
+    }
+}
+
+impl Hook for Debugger {
+    fn before_statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) {
+        let line = stmt.line();
+        if !self.stepping.should_pause(line, interpreter.frames.len()) {
+            return;
+        }
+        if self.stepping.at_breakpoint(line) {
+            println!("Breakpoint at line {}", line);
+        }
+        self.show_line(line);
+
+        let stdin = io::stdin();
+        loop {
+            print!("{}", PROMPT);
+            let _ = io::stdout().flush();
+            let mut input = String::new();
+            // end of input lets the program run to completion
+            if stdin.lock().read_line(&mut input).unwrap_or(0) == 0 {
+                self.stepping = Stepping::new(Step::Continue);
+                return;
+            }
+            let input = input.trim();
+            let (command, argument) = match input.find(char::is_whitespace) {
+                Some(index) => (&input[..index], input[index..].trim()),
+                None => (input, ""),
+            };
+            let depth = interpreter.frames.len();
+            match command {
+                "" => (),
+                "c" | "continue" => return self.stepping.resume(Step::Continue),
+                "s" | "step" => return self.stepping.resume(Step::In),
+                "n" | "next" => return self.stepping.resume(Step::Over(depth)),
+                "f" | "finish" => return self.stepping.resume(Step::Out(depth)),
+                "b" | "break" => match argument.parse::<i32>() {
+                    Ok(line) => {
+                        self.stepping.breakpoints.insert(line);
+                        println!("Breakpoint set at line {}", line);
+                    }
+                    Err(_) => println!("Expected a line number"),
+                },
+                "delete" => match argument.parse::<i32>() {
+                    Ok(line) if self.stepping.breakpoints.remove(&line) => {
+                        println!("Breakpoint at line {} removed", line)
+                    }
+                    _ => println!("No breakpoint at '{}'", argument),
+                },
+                "breakpoints" => {
+                    for line in &self.stepping.breakpoints {
+                        self.show_line(*line);
+                    }
+                }
+                "l" | "locals" => show_locals(interpreter),
+                "globals" => show_scope(&interpreter.globals.borrow().values().clone()),
+                "p" | "print" => match evaluate_in_frame(interpreter, None, argument) {
+                    Ok(value) => println!("{}", value),
+                    Err(err) => println!("{}", err),
+                },
+                "bt" | "backtrace" => {
+                    for (index, frame) in interpreter.frames.iter().rev().enumerate() {
+                        println!("#{} {} at line {}", index, frame.name, frame.line);
+                    }
+                }
+                "h" | "help" => println!("{}", HELP),
+                "q" | "quit" => exit(0),
+                _ => println!("Unknown command '{}', try help", command),
+            }
+        }
+    }
+}
+
+impl Debugger {
+    fn show_line(&self, line: i32) {
+        let text = self
+            .source_lines
+            .get(line as usize - 1)
+            .map_or("", |text| text.trim());
+        println!("[line {}] {}", line, text);
+    }
+}
+
+// every scope between the paused statement and the globals
+fn show_locals(interpreter: &Interpreter) {
+    let mut environment = interpreter.environment();
+    let mut depth = 0;
+    while !Rc::ptr_eq(&environment, &interpreter.globals) {
+        println!("scope {}:", depth);
+        show_scope(&environment.borrow().values().clone());
+        let enclosing = environment.borrow().enclosing();
+        match enclosing {
+            Some(enclosing) => environment = enclosing,
+            None => break,
+        }
+        depth += 1;
+    }
+}
+
+fn show_scope(values: &HashMap<String, Value>) {
+    let mut names = values.keys().collect::<Vec<&String>>();
+    names.sort();
+    for name in names {
+        println!("  {} = {}", name, values[name]);
+    }
+}
+
+// evaluates source typed at the prompt with a paused frame's variables in scope,
+// in the given environment or the innermost one
+pub fn evaluate_in_frame(
+    interpreter: &mut Interpreter,
+    environment: Option<Rc<RefCell<Environment>>>,
+    source: &str,
+) -> Result<Value, String> {
+    let mut tokens = Scanner::new(source).scan_tokens()?;
+    // line 0 keeps these expressions from clashing with the program's in the resolver's table
+    for token in &mut tokens {
+        token.line = 0;
+    }
+    let expr = Parser::new(tokens).parse_expression()?;
+    let environment = environment.unwrap_or_else(|| interpreter.environment());
+    let mut resolved = Vec::new();
+    resolve_in_frame(&environment, &expr, &mut resolved)?;
+    for (expr, distance) in &resolved {
+        interpreter.resolve(expr, *distance);
+    }
+
+    // calls record the environment they were made from in the innermost frame,
+    // which has to be put back as the program hasn't moved
+    let innermost = interpreter
+        .frames
+        .last()
+        .map(|frame| frame.environment.clone());
+    let result = interpreter.with_environment(environment, |interpreter| {
+        interpreter.evaluate_expression(expr)
+    });
+    if let (Some(frame), Some(environment)) = (interpreter.frames.last_mut(), innermost) {
+        frame.environment = environment;
+    }
+    // each evaluation is parsed afresh, so its entries would otherwise pile up
+    for (expr, _) in &resolved {
+        interpreter.forget(expr);
+    }
+    result
+}
+
+// the resolver works on declarations, but here the environments already exist,
+// so each name resolves to however far up the chain it is defined
+fn resolve_in_frame(
+    environment: &Rc<RefCell<Environment>>,
+    expr: &Expr,
+    resolved: &mut Vec<(Expr, usize)>,
+) -> Result<(), String> {
+    match expr {
+        Expr::Assign(name, value) => {
+            resolve_in_frame(environment, value, resolved)?;
+            resolved.push((expr.clone(), distance(environment, &name.lexeme)?));
+        }
+        Expr::Variable(name) | Expr::This(name) => {
+            resolved.push((expr.clone(), distance(environment, &name.lexeme)?));
+        }
+        Expr::Super(keyword, _) => {
+            resolved.push((expr.clone(), distance(environment, &keyword.lexeme)?));
+        }
+        Expr::Binary(left, _, right)
+        | Expr::Logical(left, _, right)
+        | Expr::Index(left, _, right) => {
+            resolve_in_frame(environment, left, resolved)?;
+            resolve_in_frame(environment, right, resolved)?;
+        }
+        Expr::Grouping(inner)
+        | Expr::Urnary(_, inner)
+        | Expr::Await(_, inner)
+        | Expr::Spawn(_, inner)
+        | Expr::Get(inner, _)
+        | Expr::OptionalGet(inner, _) => {
+            resolve_in_frame(environment, inner, resolved)?;
+        }
+        Expr::Call(callee, _, arguments) => {
+            resolve_in_frame(environment, callee, resolved)?;
+            for argument in arguments {
+                resolve_in_frame(environment, argument, resolved)?;
+            }
+        }
+        Expr::Set(object, _, value) | Expr::CompoundSet(object, _, _, value) => {
+            resolve_in_frame(environment, object, resolved)?;
+            resolve_in_frame(environment, value, resolved)?;
+        }
+        Expr::CompoundAssign(name, _, value) => {
+            resolve_in_frame(environment, value, resolved)?;
+            resolved.push((expr.clone(), distance(environment, &name.lexeme)?));
+        }
+        Expr::Conditional(condition, _, then_branch, else_branch) => {
+            resolve_in_frame(environment, condition, resolved)?;
+            resolve_in_frame(environment, then_branch, resolved)?;
+            resolve_in_frame(environment, else_branch, resolved)?;
+        }
+        Expr::Postfix(update, _) => {
+            resolve_in_frame(environment, update, resolved)?;
+        }
+        Expr::StringLiteral(_)
+        | Expr::NumberLiteral(..)
+        | Expr::IntLiteral(..)
+        | Expr::NilLiteral
+        | Expr::TrueLiteral
+        | Expr::FalseLiteral => (),
+    }
+    Ok(())
+}
+
+fn distance(environment: &Rc<RefCell<Environment>>, name: &str) -> Result<usize, String> {
+    let mut environment = environment.clone();
+    let mut distance = 0;
+    loop {
+        if environment.borrow().values().contains_key(name) {
+            return Ok(distance);
+        }
+        let enclosing = environment.borrow().enclosing();
+        match enclosing {
+            Some(enclosing) => environment = enclosing,
+            None => return Err(format!("Undefined variable '{}'", name)),
+        }
+        distance += 1;
+    }
+}
@@ -20,21 +20,29 @@ impl Environment {
         self.values.insert(name, value);
     }
 
+    pub fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
+
+    pub fn enclosing(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.enclosing.clone()
+    }
+
     pub fn get(&self, name: String) -> Result<Value, String> {
         // println!("getting {} from {:?}", name, self);
         match self.values.get(&name) {
             Some(value) => Ok(value.clone()),
             None => match &self.enclosing {
                 Some(enclosing) => enclosing.borrow().get(name),
-                None => Err(format!("Undefined variable: {}", name)),
+                None => Err(format!("Undefined variable '{}'", name)),
             },
         }
     }
 
     pub fn assign(&mut self, name: String, value: Value) -> Result<(), String> {
         // println!("setting {} in {:?} to {:?}", name.lexeme, self, value);
-        if self.values.contains_key(&name) {
-            self.values.insert(name, value);
+        if let Some(existing) = self.values.get_mut(&name) {
+            *existing = value;
             return Ok(());
         }
 
@@ -47,26 +55,42 @@ impl Environment {
             return Ok(());
         };
 
-        Err(format!("Undefined variable: '{}'", name))
+        Err(format!("Undefined variable '{}'", name))
     }
 
     pub fn get_at(&self, distance: usize, name: String) -> Result<Value, String> {
-        match self.ancestor(distance).values.get(&name) {
-            Some(value) => Ok(value.clone()),
-            None => Err("Failed to get value".to_string()),
-        }
+        let value = if distance == 0 {
+            self.values.get(&name).cloned()
+        } else {
+            self.ancestor(distance).borrow().values.get(&name).cloned()
+        };
+        value.ok_or(format!("Undefined variable '{}'", name))
     }
 
-    pub fn assign_at(&self, distance: usize, name: String, value: Value) {
-        self.ancestor(distance)
-            .values
-            .insert(name.clone(), value.clone());
+    pub fn assign_at(&mut self, distance: usize, name: String, value: Value) {
+        if distance == 0 {
+            self.values.insert(name, value);
+        } else {
+            self.ancestor(distance)
+                .borrow_mut()
+                .values
+                .insert(name, value);
+        }
     }
 
-    fn ancestor(&self, distance: usize) -> Environment {
-        let mut environment = (*self).clone();
-        for _ in 0..distance {
-            environment = environment.enclosing.unwrap().clone().borrow().clone();
+    // the resolver guarantees the chain is at least this long
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = self
+            .enclosing
+            .clone()
+            .expect("resolved distance is past the global scope");
+        for _ in 1..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolved distance is past the global scope");
+            environment = enclosing;
         }
         environment
     }
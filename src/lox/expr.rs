@@ -9,17 +9,36 @@ pub enum Expr {
     Grouping(Box<Expr>),
     Urnary(Token, Box<Expr>),
     StringLiteral(String),
-    NumberLiteral(f64),
+    // the spelling in the source, which the formatter keeps; None for literals the
+    // parser or optimizer made up
+    NumberLiteral(f64, Option<String>),
+    IntLiteral(i64, Option<String>),
     NilLiteral,
     TrueLiteral,
     FalseLiteral,
     Variable(Token),
+    // "and", "or" and "??"
     Logical(Box<Expr>, Token, Box<Expr>),
+    // "cond ? a : b", keeping the "?" token for its line
+    Conditional(Box<Expr>, Token, Box<Expr>, Box<Expr>),
     Call(Box<Expr>, Token, Vec<Expr>),
+    // "obj[index]", keeping the "]" token for its line
+    Index(Box<Expr>, Token, Box<Expr>),
     Get(Box<Expr>, Token),
+    // "obj?.name", nil instead of an error when obj is nil
+    OptionalGet(Box<Expr>, Token),
     Set(Box<Expr>, Token, Box<Expr>),
+    // "+=" and friends, and prefix "++"/"--" with a value of 1
+    CompoundAssign(Token, Token, Box<Expr>),
+    CompoundSet(Box<Expr>, Token, Token, Box<Expr>),
+    // "x++" and "x--", wrapping the compound expression they update through
+    Postfix(Box<Expr>, Token),
     This(Token),
     Super(Token, Token),
+    // "await task", waiting for a task to finish and giving its value
+    Await(Token, Box<Expr>),
+    // "spawn f(args)", always wrapping a call, run as a task rather than right away
+    Spawn(Token, Box<Expr>),
 }
 
 impl std::hash::Hash for Expr {
@@ -37,15 +56,18 @@ impl std::hash::Hash for Expr {
             Expr::Grouping(a) => {
                 a.hash(state);
             }
-            Expr::Urnary(a, b) => {
+            Expr::Urnary(a, b) | Expr::Await(a, b) | Expr::Spawn(a, b) => {
                 a.hash(state);
                 b.hash(state);
             }
             Expr::StringLiteral(a) => {
                 format!("Expr::StringLiteral{}", a).hash(state);
             }
-            Expr::NumberLiteral(a) => {
-                format!("Expr::NumberLiteral{}", a.to_string()).hash(state);
+            Expr::NumberLiteral(a, _) => {
+                format!("Expr::NumberLiteral{}", a).hash(state);
+            }
+            Expr::IntLiteral(a, _) => {
+                format!("Expr::IntLiteral{}", a).hash(state);
             }
             Expr::NilLiteral => {
                 "Expr::NilLiteral".hash(state);
@@ -69,15 +91,41 @@ impl std::hash::Hash for Expr {
                 b.hash(state);
                 c.hash(state);
             }
-            Expr::Get(a, b) => {
+            Expr::Index(a, b, c) => {
                 a.hash(state);
                 b.hash(state);
+                c.hash(state);
+            }
+            Expr::Get(a, b) | Expr::OptionalGet(a, b) => {
+                a.hash(state);
+                b.hash(state);
+            }
+            Expr::Conditional(a, b, c, d) => {
+                a.hash(state);
+                b.hash(state);
+                c.hash(state);
+                d.hash(state);
             }
             Expr::Set(a, b, c) => {
                 a.hash(state);
                 b.hash(state);
                 c.hash(state);
             }
+            Expr::CompoundAssign(a, b, c) => {
+                a.hash(state);
+                b.hash(state);
+                c.hash(state);
+            }
+            Expr::CompoundSet(a, b, c, d) => {
+                a.hash(state);
+                b.hash(state);
+                c.hash(state);
+                d.hash(state);
+            }
+            Expr::Postfix(a, b) => {
+                a.hash(state);
+                b.hash(state);
+            }
             Expr::This(a) => {
                 a.hash(state);
             }
@@ -101,7 +149,8 @@ impl Display for Expr {
             Expr::Grouping(expression) => write!(f, "(grouping {})", expression),
             Expr::Urnary(operator, right) => write!(f, "(urnary {} {})", operator, right),
             Expr::StringLiteral(literal) => write!(f, "(literal {})", literal),
-            Expr::NumberLiteral(literal) => write!(f, "(literal {})", literal),
+            Expr::NumberLiteral(literal, _) => write!(f, "(literal {})", literal),
+            Expr::IntLiteral(literal, _) => write!(f, "(literal {})", literal),
             Expr::NilLiteral => write!(f, "(literal nil)"),
             Expr::TrueLiteral => write!(f, "(literal true)"),
             Expr::FalseLiteral => write!(f, "(literal false)"),
@@ -116,18 +165,44 @@ impl Display for Expr {
                 }
                 write!(f, ")")
             }
+            Expr::Index(object, _, index) => write!(f, "(index {} {})", object, index),
             Expr::Get(object, name) => {
                 write!(f, "(get {}.{})", object, name.lexeme)
             }
+            Expr::OptionalGet(object, name) => {
+                write!(f, "(get {}?.{})", object, name.lexeme)
+            }
+            Expr::Conditional(condition, _, then_branch, else_branch) => {
+                write!(
+                    f,
+                    "(conditional {} {} {})",
+                    condition, then_branch, else_branch
+                )
+            }
             Expr::Set(object, name, value) => {
                 write!(f, "(set {}.{} = {})", object, name.lexeme, value)
             }
+            Expr::CompoundAssign(name, operator, value) => {
+                write!(f, "(assign {} {} {})", name.lexeme, operator.lexeme, value)
+            }
+            Expr::CompoundSet(object, name, operator, value) => {
+                write!(
+                    f,
+                    "(set {}.{} {} {})",
+                    object, name.lexeme, operator.lexeme, value
+                )
+            }
+            Expr::Postfix(target, operator) => {
+                write!(f, "(postfix {} {})", target, operator.lexeme)
+            }
             Expr::This(keyword) => {
                 write!(f, "(this {})", keyword)
             }
             Expr::Super(keyword, method) => {
                 write!(f, "(super {} {})", keyword, method)
             }
+            Expr::Await(_, task) => write!(f, "(await {})", task),
+            Expr::Spawn(_, call) => write!(f, "(spawn {})", call),
         }
     }
 }
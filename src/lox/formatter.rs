@@ -0,0 +1,476 @@
+use std::fs;
+
+use super::{
+    expr::Expr,
+    parser::Parser,
+    scanner::Scanner,
+    stmt::{MethodKind, Stmt},
+    token::Token,
+    token_type::TokenType,
+};
+
+const INDENT: &str = "  ";
+
+// formats each file in place, or with check just reports the ones that would change
+// returns false if any file failed to format or needs formatting
+pub fn format_files(paths: &[String], check: bool) -> bool {
+    let mut all_formatted = true;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                println!("Could not read '{}': {}", path, err);
+                all_formatted = false;
+                continue;
+            }
+        };
+        match format_source(&source) {
+            Ok(formatted) if formatted == source => (),
+            Ok(formatted) => {
+                if check {
+                    println!("Would reformat {}", path);
+                    all_formatted = false;
+                } else if let Err(err) = fs::write(path, formatted) {
+                    println!("Could not write '{}': {}", path, err);
+                    all_formatted = false;
+                }
+            }
+            Err(err) => {
+                println!("{}: {}", path, err);
+                all_formatted = false;
+            }
+        }
+    }
+    all_formatted
+}
+
+pub fn format_source(source: &str) -> Result<String, String> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    let braces = match_braces(&tokens);
+    let stmts = Parser::new(tokens).parse()?;
+
+    let mut formatter = Formatter {
+        source_lines: source.lines().collect(),
+        comments: scanner.comments(),
+        next_comment: 0,
+        braces,
+        next_brace: 0,
+        output: String::new(),
+        line: String::new(),
+        indent: 0,
+    };
+    formatter.statements(&stmts, false);
+    formatter.flush_comments(i32::MAX);
+    Ok(formatter.output)
+}
+
+// (opening line, closing line) of every brace pair, in the order the '{'s appear
+// the AST doesn't keep closing braces, so this is how comments before a '}' stay inside it
+fn match_braces(tokens: &[Token]) -> Vec<(i32, i32)> {
+    let mut braces = Vec::new();
+    let mut open = Vec::new();
+    for token in tokens {
+        match token.token_type {
+            TokenType::LeftBrace => {
+                open.push(braces.len());
+                braces.push((token.line, token.line));
+            }
+            TokenType::RightBrace => {
+                if let Some(index) = open.pop() {
+                    braces[index].1 = token.line;
+                }
+            }
+            _ => (),
+        }
+    }
+    braces
+}
+
+struct Formatter<'a> {
+    source_lines: Vec<&'a str>,
+    comments: Vec<Token>,
+    next_comment: usize,
+    braces: Vec<(i32, i32)>,
+    next_brace: usize,
+    output: String,
+    // the line being built, empty at the start of a line
+    line: String,
+    indent: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn statements(&mut self, stmts: &[Stmt], methods: bool) {
+        for stmt in stmts {
+            self.flush_comments(stmt.line());
+            self.keep_blank_line(stmt.line());
+            match stmt {
+                Stmt::FunctionDeclaration(_, _, _, signature) if methods => {
+                    let prefix = match signature.kind {
+                        MethodKind::Static => "class ",
+                        MethodKind::Setter => "set ",
+                        MethodKind::Method | MethodKind::Getter => "",
+                    };
+                    self.function(stmt, prefix);
+                }
+                _ => self.statement(stmt),
+            }
+        }
+    }
+
+    // writes the statement starting on the current line, and ends the line
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(keyword, stmts) if keyword.token_type == TokenType::For => {
+                if let [initializer, loop_stmt] = &stmts[..] {
+                    self.for_loop(Some(initializer), loop_stmt);
+                }
+            }
+            Stmt::While(keyword, _, _) if keyword.token_type == TokenType::For => {
+                self.for_loop(None, stmt)
+            }
+            Stmt::Block(_, stmts) => {
+                let close = self.block(stmts, false);
+                self.end_line(close);
+            }
+            Stmt::Expression(first, expr) => {
+                self.write(&format!("{};", format_expr(expr)));
+                self.end_line(first.line);
+            }
+            Stmt::Print(keyword, expr) => {
+                self.write(&format!("print {};", format_expr(expr)));
+                self.end_line(keyword.line);
+            }
+            Stmt::VariableDeclaration(name, annotation, initializer) => {
+                self.write(&format_variable(name, annotation, initializer));
+                self.end_line(name.line);
+            }
+            Stmt::Return(keyword, value) => {
+                if **value == Expr::NilLiteral {
+                    self.write("return;");
+                } else {
+                    self.write(&format!("return {};", format_expr(value)));
+                }
+                self.end_line(keyword.line);
+            }
+            Stmt::Yield(keyword, value) => {
+                self.write(&format!("yield {};", format_expr(value)));
+                self.end_line(keyword.line);
+            }
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => {
+                self.write(&format!("{};", keyword.lexeme));
+                self.end_line(keyword.line);
+            }
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                self.write(&format!("if ({}) ", format_expr(condition)));
+                match &**else_branch {
+                    Some(else_stmt) => {
+                        self.branch(then_branch, true);
+                        self.write("else ");
+                        if let Stmt::If(_, _, _, _) = else_stmt {
+                            self.statement(else_stmt);
+                        } else {
+                            self.branch(else_stmt, false);
+                        }
+                    }
+                    None => self.branch(then_branch, false),
+                }
+            }
+            Stmt::While(_, condition, body) => {
+                self.write(&format!("while ({}) ", format_expr(condition)));
+                self.branch(body, false);
+            }
+            Stmt::ForIn(_, name, iterable, body) => {
+                self.write(&format!(
+                    "for (var {} in {}) ",
+                    name.lexeme,
+                    format_expr(iterable)
+                ));
+                self.branch(body, false);
+            }
+            Stmt::FunctionDeclaration(_, _, _, _) => self.function(stmt, "fun "),
+            Stmt::ClassDeclaration(name, superclass, traits, methods) => {
+                self.write(&format!("class {} ", name.lexeme));
+                if let Some(superclass) = superclass {
+                    self.write(&format!("< {} ", format_expr(superclass)));
+                }
+                if !traits.is_empty() {
+                    let traits = traits.iter().map(format_expr).collect::<Vec<String>>();
+                    self.write(&format!("with {} ", traits.join(", ")));
+                }
+                let close = self.block(methods, true);
+                self.end_line(close);
+            }
+            Stmt::TraitDeclaration(name, methods) => {
+                self.write(&format!("trait {} ", name.lexeme));
+                let close = self.block(methods, true);
+                self.end_line(close);
+            }
+        }
+    }
+
+    // a for loop was desugared by the parser into
+    // { initializer; while (condition) { body; increment; } }
+    fn for_loop(&mut self, initializer: Option<&Stmt>, loop_stmt: &Stmt) {
+        if let Stmt::While(_, condition, body) = loop_stmt {
+            let (body, increment) = match &**body {
+                Stmt::Block(keyword, stmts) if keyword.token_type == TokenType::For => {
+                    match &stmts[..] {
+                        [body, Stmt::Expression(_, increment)] => (body, Some(increment)),
+                        _ => (&**body, None),
+                    }
+                }
+                _ => (&**body, None),
+            };
+            let initializer = match initializer {
+                Some(Stmt::VariableDeclaration(name, annotation, value)) => {
+                    format_variable(name, annotation, value)
+                }
+                Some(Stmt::Expression(_, expr)) => format!("{};", format_expr(expr)),
+                _ => ";".to_string(),
+            };
+            let condition = match &**condition {
+                Expr::TrueLiteral => "".to_string(),
+                condition => format!(" {}", format_expr(condition)),
+            };
+            let increment = match increment {
+                Some(increment) => format!(" {}", format_expr(increment)),
+                None => "".to_string(),
+            };
+            self.write(&format!(
+                "for ({}{};{}) ",
+                initializer, condition, increment
+            ));
+            self.branch(body, false);
+        }
+    }
+
+    // the body of an if/else/while/for, which shares the line with its header
+    fn branch(&mut self, stmt: &Stmt, followed_by_else: bool) {
+        match stmt {
+            Stmt::Block(keyword, stmts) if keyword.token_type == TokenType::LeftBrace => {
+                let close = self.block(stmts, false);
+                if followed_by_else {
+                    self.write(" ");
+                } else {
+                    self.end_line(close);
+                }
+            }
+            _ => self.statement(stmt),
+        }
+    }
+
+    fn function(&mut self, stmt: &Stmt, prefix: &str) {
+        if let Stmt::FunctionDeclaration(name, params, body, signature) = stmt {
+            let params = params
+                .iter()
+                .zip(&signature.parameters)
+                .map(|(param, annotation)| {
+                    format!("{}{}", param.lexeme, format_annotation(annotation))
+                })
+                .collect::<Vec<String>>();
+            // getters are the only functions declared without parentheses
+            let params = match signature.kind {
+                MethodKind::Getter => "".to_string(),
+                _ => format!("({})", params.join(", ")),
+            };
+            self.write(&format!(
+                "{}{}{}{} ",
+                prefix,
+                name.lexeme,
+                params,
+                format_annotation(&signature.returns)
+            ));
+            let close = self.block(body, false);
+            self.end_line(close);
+        }
+    }
+
+    // writes a braced block, leaving the line open after the '}'
+    // returns the line of the '}' in the source
+    fn block(&mut self, stmts: &[Stmt], methods: bool) -> i32 {
+        let (open, close) = self.braces[self.next_brace];
+        self.next_brace += 1;
+
+        let has_comments = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.line < close);
+        if stmts.is_empty() && !has_comments {
+            self.write("{}");
+            return close;
+        }
+
+        self.write("{");
+        self.end_line(open);
+        self.indent += 1;
+        self.statements(stmts, methods);
+        self.flush_comments(close);
+        self.indent -= 1;
+        self.write("}");
+        close
+    }
+
+    // comments before the given line get a line of their own
+    fn flush_comments(&mut self, before: i32) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.line >= before {
+                break;
+            }
+            let comment = comment.clone();
+            self.next_comment += 1;
+            self.keep_blank_line(comment.line);
+            self.write(&comment.lexeme);
+            self.finish_line();
+        }
+    }
+
+    // one blank line is kept wherever the source had at least one
+    fn keep_blank_line(&mut self, line: i32) {
+        let previous_is_blank = line >= 2
+            && self
+                .source_lines
+                .get(line as usize - 2)
+                .is_some_and(|previous| previous.trim().is_empty());
+        if previous_is_blank
+            && !self.output.is_empty()
+            && !self.output.ends_with("\n\n")
+            && !self.output.ends_with("{\n")
+        {
+            self.output.push('\n');
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line.is_empty() {
+            self.line = INDENT.repeat(self.indent);
+        }
+        self.line.push_str(text);
+    }
+
+    // comments up to the given source line trail the code on this line
+    fn end_line(&mut self, source_line: i32) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.line > source_line {
+                break;
+            }
+            self.line.push(' ');
+            self.line.push_str(&comment.lexeme.clone());
+            self.next_comment += 1;
+        }
+        self.finish_line();
+    }
+
+    fn finish_line(&mut self) {
+        self.output.push_str(self.line.trim_end());
+        self.output.push('\n');
+        self.line.clear();
+    }
+}
+
+fn format_variable(name: &Token, annotation: &Option<Token>, initializer: &Expr) -> String {
+    let annotation = format_annotation(annotation);
+    if *initializer == Expr::NilLiteral {
+        format!("var {}{};", name.lexeme, annotation)
+    } else {
+        format!(
+            "var {}{} = {};",
+            name.lexeme,
+            annotation,
+            format_expr(initializer)
+        )
+    }
+}
+
+fn format_annotation(annotation: &Option<Token>) -> String {
+    match annotation {
+        Some(annotation) => format!(": {}", annotation.lexeme),
+        None => "".to_string(),
+    }
+}
+
+// the parser keeps groupings, so printing the tree as-is gives back the same tree
+pub fn format_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign(name, value) => format!("{} = {}", name.lexeme, format_expr(value)),
+        Expr::Binary(left, operator, right) | Expr::Logical(left, operator, right) => format!(
+            "{} {} {}",
+            format_expr(left),
+            operator.lexeme,
+            format_expr(right)
+        ),
+        Expr::Grouping(expression) => format!("({})", format_expr(expression)),
+        Expr::Urnary(operator, right) => {
+            let right = format_expr(right);
+            // keep "- -a" from turning into "--a"
+            if right.starts_with(&operator.lexeme) {
+                format!("{} {}", operator.lexeme, right)
+            } else {
+                format!("{}{}", operator.lexeme, right)
+            }
+        }
+        Expr::Await(keyword, task) => format!("{} {}", keyword.lexeme, format_expr(task)),
+        Expr::Spawn(keyword, call) => format!("{} {}", keyword.lexeme, format_expr(call)),
+        Expr::StringLiteral(literal) => format!("\"{}\"", literal),
+        // numbers are written as they were, 0xff and 1_000 included
+        Expr::NumberLiteral(_, Some(spelling)) | Expr::IntLiteral(_, Some(spelling)) => {
+            spelling.clone()
+        }
+        // a whole float keeps its ".0" so it doesn't come back as an int
+        Expr::NumberLiteral(literal, None) if literal.fract() == 0.0 => format!("{:.1}", literal),
+        Expr::NumberLiteral(literal, None) => literal.to_string(),
+        Expr::IntLiteral(literal, None) => literal.to_string(),
+        Expr::NilLiteral => "nil".to_string(),
+        Expr::TrueLiteral => "true".to_string(),
+        Expr::FalseLiteral => "false".to_string(),
+        Expr::Variable(name) => name.lexeme.clone(),
+        Expr::Call(callee, _, arguments) => format!(
+            "{}({})",
+            format_expr(callee),
+            arguments
+                .iter()
+                .map(format_expr)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Expr::Index(object, _, index) => format!("{}[{}]", format_expr(object), format_expr(index)),
+        Expr::Get(object, name) => format!("{}.{}", format_expr(object), name.lexeme),
+        Expr::OptionalGet(object, name) => format!("{}?.{}", format_expr(object), name.lexeme),
+        Expr::Conditional(condition, _, then_branch, else_branch) => format!(
+            "{} ? {} : {}",
+            format_expr(condition),
+            format_expr(then_branch),
+            format_expr(else_branch)
+        ),
+        Expr::Set(object, name, value) => format!(
+            "{}.{} = {}",
+            format_expr(object),
+            name.lexeme,
+            format_expr(value)
+        ),
+        Expr::CompoundAssign(name, operator, value) => format_update(&name.lexeme, operator, value),
+        Expr::CompoundSet(object, name, operator, value) => format_update(
+            &format!("{}.{}", format_expr(object), name.lexeme),
+            operator,
+            value,
+        ),
+        Expr::Postfix(update, operator) => match &**update {
+            Expr::CompoundAssign(name, _, _) => format!("{}{}", name.lexeme, operator.lexeme),
+            Expr::CompoundSet(object, name, _, _) => {
+                format!("{}.{}{}", format_expr(object), name.lexeme, operator.lexeme)
+            }
+            update => format!("{}{}", format_expr(update), operator.lexeme),
+        },
+        Expr::This(_) => "this".to_string(),
+        Expr::Super(_, method) => format!("super.{}", method.lexeme),
+    }
+}
+
+// "++x" for increments, "x += 1" for everything else
+fn format_update(target: &str, operator: &Token, value: &Expr) -> String {
+    if operator.is_increment() {
+        format!("{}{}", operator.lexeme, target)
+    } else {
+        format!("{} {} {}", target, operator.lexeme, format_expr(value))
+    }
+}
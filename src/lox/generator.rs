@@ -0,0 +1,550 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use super::{
+    callable::NativeFunction,
+    environment::Environment,
+    expr::Expr,
+    interpreter::{for_increment, Interpreter, Iteration, Unwind},
+    stmt::Stmt,
+    task::Task,
+    token::Token,
+    value::Value,
+};
+
+// what calling a function with a yield in its body gives; the body runs a piece at a
+// time, up to its next yield, whenever a value is asked for; a spawned task's body
+// runs the same way, stopping at each await instead
+#[derive(Clone)]
+pub struct Generator {
+    name: Token,
+    state: Rc<RefCell<State>>,
+}
+
+struct State {
+    // where the body picks up again, innermost last, empty once it has finished
+    frames: Vec<Resume>,
+    // a value hasNext ran ahead to, handed out by the next call to next
+    ahead: Option<Value>,
+    running: bool,
+    // whether the body is a task's, which stops at awaits rather than yields
+    task: bool,
+    // what to do with the value an await gives once the task goes on
+    pending: Option<(Action, Rc<RefCell<Environment>>)>,
+}
+
+// how far the body got when it stopped
+pub enum Outcome {
+    Yielded(Value),
+    Awaiting(Task),
+    Finished(Value),
+}
+
+// a task only stops at awaits it can pick up from, those that are the whole of a
+// statement, a variable's initializer or the value returned; calls there can stop too
+enum Action {
+    Discard,
+    Define(Token),
+    Return,
+}
+
+// the statements that can hold a yield are taken apart into frames, so the rust
+// stack is empty whenever the body is suspended; the rest run in one go
+enum Resume {
+    // statements still to run and the environment they run in
+    Block(Vec<Stmt>, usize, Rc<RefCell<Environment>>),
+    // a loop checks its condition whenever its body finishes
+    While {
+        keyword: Token,
+        condition: Expr,
+        body: Stmt,
+        environment: Rc<RefCell<Environment>>,
+        started: bool,
+    },
+    ForIn {
+        keyword: Token,
+        name: Token,
+        body: Stmt,
+        iteration: Iteration,
+        environment: Rc<RefCell<Environment>>,
+        started: bool,
+    },
+    // a function a task called, run as a task of its own until it returns
+    Call(Generator, Action, Rc<RefCell<Environment>>),
+}
+
+// what running one statement of the body came to
+enum Step {
+    Next,
+    Yielded(Box<Value>),
+    Awaiting(Task, Action),
+    Finished(Box<Value>),
+    Break,
+    Continue,
+}
+
+impl Generator {
+    pub fn new(name: Token, body: Vec<Stmt>, environment: Rc<RefCell<Environment>>) -> Self {
+        Self::start(name, body, environment, false)
+    }
+
+    pub fn task(name: Token, body: Vec<Stmt>, environment: Rc<RefCell<Environment>>) -> Self {
+        Self::start(name, body, environment, true)
+    }
+
+    fn start(
+        name: Token,
+        body: Vec<Stmt>,
+        environment: Rc<RefCell<Environment>>,
+        task: bool,
+    ) -> Self {
+        Self {
+            name,
+            state: Rc::new(RefCell::new(State {
+                frames: vec![Resume::Block(body, 0, environment)],
+                ahead: None,
+                running: false,
+                task,
+                pending: None,
+            })),
+        }
+    }
+
+    // the next value the body yields, none once it has returned
+    pub fn next(&self, interpreter: &mut Interpreter) -> Result<Option<Value>, String> {
+        let ahead = self.state.borrow_mut().ahead.take();
+        match ahead {
+            Some(value) => Ok(Some(value)),
+            None => self.generate(interpreter),
+        }
+    }
+
+    // runs ahead to the next yield to find out if there is one
+    pub fn has_next(&self, interpreter: &mut Interpreter) -> Result<bool, String> {
+        if self.state.borrow().ahead.is_some() {
+            return Ok(true);
+        }
+        let value = self.generate(interpreter)?;
+        let has_next = value.is_some();
+        self.state.borrow_mut().ahead = value;
+        Ok(has_next)
+    }
+
+    // next() gives nil once the body has finished, hasNext() tells the two apart
+    pub fn method(&self, name: &str) -> Option<NativeFunction> {
+        let method = match name {
+            "next" => NativeFunction::new("next".to_string(), 0, |interpreter, arguments| {
+                let value = receiver(&arguments).next(interpreter)?;
+                Ok(value.unwrap_or(Value::Nil))
+            }),
+            "hasNext" => NativeFunction::new("hasNext".to_string(), 0, |interpreter, arguments| {
+                Ok(Value::Bool(receiver(&arguments).has_next(interpreter)?))
+            }),
+            _ => return None,
+        };
+        Some(method.bind(Value::Generator(self.clone())))
+    }
+
+    fn generate(&self, interpreter: &mut Interpreter) -> Result<Option<Value>, String> {
+        match self.resume(interpreter, Value::Nil)? {
+            Outcome::Yielded(value) => Ok(Some(value)),
+            Outcome::Awaiting(_) | Outcome::Finished(_) => Ok(None),
+        }
+    }
+
+    // runs the body up to where it next stops, a task going on with the value it awaited
+    pub fn resume(&self, interpreter: &mut Interpreter, sent: Value) -> Result<Outcome, String> {
+        let (mut frames, pending, task) = {
+            let mut state = self.state.borrow_mut();
+            if state.running {
+                return Err(format!(
+                    "Generator '{}' is already running",
+                    self.name.lexeme
+                ));
+            }
+            state.running = true;
+            let frames = std::mem::take(&mut state.frames);
+            (frames, state.pending.take(), state.task)
+        };
+        let mut next_pending = None;
+        // without an await to hand it to, the value goes to the call that stopped
+        let (returned, sent) = match pending {
+            Some((action, environment)) => (settle(action, &environment, sent), Value::Nil),
+            None => (None, sent),
+        };
+        let result = match returned {
+            // returning what was awaited leaves nothing more to run
+            Some(value) => Ok(Outcome::Finished(value)),
+            None => {
+                interpreter.push_frame(self.name.lexeme.clone(), self.name.line);
+                let result = run(&mut frames, &mut next_pending, task, sent, interpreter);
+                interpreter.pop_frame();
+                result
+            }
+        };
+        let mut state = self.state.borrow_mut();
+        state.running = false;
+        // a body that failed or finished doesn't start again
+        if let Ok(Outcome::Yielded(_) | Outcome::Awaiting(_)) = result {
+            state.frames = frames;
+            state.pending = next_pending;
+        }
+        result
+    }
+}
+
+// next and hasNext are bound to the generator, which comes first
+fn receiver(arguments: &[Value]) -> &Generator {
+    match arguments.first() {
+        Some(Value::Generator(generator)) => generator,
+        _ => unreachable!("next and hasNext are only bound to generators"),
+    }
+}
+
+// hands an awaited or returned value to the statement that was waiting for it,
+// giving it back when the statement returns it
+fn settle(action: Action, environment: &Rc<RefCell<Environment>>, value: Value) -> Option<Value> {
+    match action {
+        Action::Discard => None,
+        Action::Define(name) => {
+            environment.borrow_mut().define(name.lexeme, value);
+            None
+        }
+        Action::Return => Some(value),
+    }
+}
+
+// runs the body until it yields, awaits or finishes, sending a call it's inside of
+// the value it went on with
+fn run(
+    frames: &mut Vec<Resume>,
+    pending: &mut Option<(Action, Rc<RefCell<Environment>>)>,
+    task: bool,
+    sent: Value,
+    interpreter: &mut Interpreter,
+) -> Result<Outcome, String> {
+    let mut sent = Some(sent);
+    while let Some(frame) = frames.last_mut() {
+        match frame {
+            Resume::Block(statements, next, environment) => {
+                let stmt = match statements.get(*next) {
+                    Some(stmt) => stmt.clone(),
+                    None => {
+                        frames.pop();
+                        continue;
+                    }
+                };
+                *next += 1;
+                let environment = environment.clone();
+                match step(stmt, environment.clone(), frames, task, interpreter)? {
+                    Step::Next => (),
+                    Step::Yielded(value) => return Ok(Outcome::Yielded(*value)),
+                    Step::Awaiting(awaited, action) => {
+                        *pending = Some((action, environment));
+                        return Ok(Outcome::Awaiting(awaited));
+                    }
+                    Step::Finished(value) => return Ok(Outcome::Finished(*value)),
+                    Step::Break => leave_loop(frames, false),
+                    Step::Continue => leave_loop(frames, true),
+                }
+            }
+            Resume::While {
+                keyword,
+                condition,
+                body,
+                environment,
+                started,
+            } => {
+                let environment = environment.clone();
+                let condition = condition.clone();
+                let value = interpreter.with_environment(environment.clone(), |interpreter| {
+                    interpreter.evaluate_expression(condition)
+                })?;
+                let entered = interpreter.is_truthy(value);
+                if !*started {
+                    *started = true;
+                    interpreter.branch(keyword, if entered { 0 } else { 1 });
+                }
+                if entered {
+                    let body = Resume::Block(vec![body.clone()], 0, environment);
+                    frames.push(body);
+                } else {
+                    frames.pop();
+                }
+            }
+            Resume::ForIn {
+                keyword,
+                name,
+                body,
+                iteration,
+                environment,
+                started,
+            } => {
+                let next = iteration.next(interpreter, keyword)?;
+                if !*started {
+                    *started = true;
+                    interpreter.branch(keyword, if next.is_some() { 0 } else { 1 });
+                }
+                match next {
+                    Some(value) => {
+                        let mut scope = Environment::new(HashMap::new(), Some(environment.clone()));
+                        scope.define(name.lexeme.clone(), value);
+                        let body =
+                            Resume::Block(vec![body.clone()], 0, Rc::new(RefCell::new(scope)));
+                        frames.push(body);
+                    }
+                    None => {
+                        frames.pop();
+                    }
+                }
+            }
+            Resume::Call(body, _, _) => {
+                let body = body.clone();
+                let value = match body.resume(interpreter, sent.take().unwrap_or(Value::Nil))? {
+                    Outcome::Finished(value) => value,
+                    // the call stays on top, to pick up again with what it awaited
+                    Outcome::Awaiting(awaited) => return Ok(Outcome::Awaiting(awaited)),
+                    Outcome::Yielded(_) => unreachable!("tasks can't yield"),
+                };
+                if let Some(Resume::Call(_, action, environment)) = frames.pop() {
+                    if let Some(value) = settle(action, &environment, value) {
+                        return Ok(Outcome::Finished(value));
+                    }
+                }
+            }
+        }
+    }
+    Ok(Outcome::Finished(Value::Nil))
+}
+
+// drops the frames inside the innermost loop, and the loop too when breaking out
+// of it; a desugared for loop's increment still runs when its body continues
+fn leave_loop(frames: &mut Vec<Resume>, continuing: bool) {
+    while let Some(frame) = frames.last_mut() {
+        match frame {
+            Resume::While { .. } | Resume::ForIn { .. } => {
+                if !continuing {
+                    frames.pop();
+                }
+                return;
+            }
+            Resume::Block(statements, next, _)
+                if continuing
+                    && for_increment(statements).is_some()
+                    && *next < statements.len() =>
+            {
+                *next = statements.len() - 1;
+                return;
+            }
+            Resume::Block(..) | Resume::Call(..) => {
+                frames.pop();
+            }
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn step(
+    stmt: Stmt,
+    environment: Rc<RefCell<Environment>>,
+    frames: &mut Vec<Resume>,
+    task: bool,
+    interpreter: &mut Interpreter,
+) -> Result<Step, String> {
+    if !pauses(&stmt, task) {
+        return match interpreter
+            .with_environment(environment, |interpreter| interpreter.execute(stmt))
+        {
+            Ok(()) => Ok(Step::Next),
+            Err(Unwind::Return(value)) => Ok(Step::Finished(Box::new(value))),
+            Err(Unwind::Error(err)) => Err(err),
+            Err(Unwind::Break) => Ok(Step::Break),
+            Err(Unwind::Continue) => Ok(Step::Continue),
+        };
+    }
+    interpreter.with_environment(environment.clone(), |interpreter| {
+        if !matches!(stmt, Stmt::Block(_, _)) {
+            interpreter.before_statement(&stmt);
+        }
+    });
+    if let Some((callee, paren, arguments, action)) = calls(&stmt) {
+        let callee = interpreter.with_environment(environment.clone(), |interpreter| {
+            interpreter.link(callee.clone())
+        })?;
+        let value = match callee {
+            // "?." earlier in the chain met nil
+            None => Value::Nil,
+            Some(Value::Function(function)) if function.suspends() => {
+                let mut values = Vec::new();
+                for argument in arguments {
+                    values.push(
+                        interpreter.with_environment(environment.clone(), |interpreter| {
+                            interpreter.evaluate_expression(argument.clone())
+                        })?,
+                    );
+                }
+                let (_, body) = function
+                    .task(values)
+                    .map_err(|err| format!("[line {}] {}", paren.line, err))?;
+                frames.push(Resume::Call(body, action, environment));
+                return Ok(Step::Next);
+            }
+            Some(callee) => interpreter.with_environment(environment.clone(), |interpreter| {
+                interpreter.call_value(callee, paren.clone(), arguments.clone())
+            })?,
+        };
+        return Ok(match settle(action, &environment, value) {
+            Some(value) => Step::Finished(Box::new(value)),
+            None => Step::Next,
+        });
+    }
+    if let Some((keyword, awaited, action)) = awaits(&stmt) {
+        let awaited = interpreter.with_environment(environment, |interpreter| {
+            interpreter.evaluate_expression(awaited.clone())
+        })?;
+        return match awaited {
+            Value::Task(awaited) => Ok(Step::Awaiting(awaited, action)),
+            awaited => Err(format!(
+                "[line {}] Can only await tasks, got {}",
+                keyword.line,
+                awaited.describe_type()
+            )),
+        };
+    }
+    match stmt {
+        Stmt::Yield(keyword, _) if task => {
+            Err(format!("[line {}] Can't yield from a task", keyword.line))
+        }
+        Stmt::Yield(_, value) => {
+            let value = interpreter.with_environment(environment, |interpreter| {
+                interpreter.evaluate_expression(*value)
+            })?;
+            Ok(Step::Yielded(Box::new(value)))
+        }
+        Stmt::Block(_, statements) => {
+            let scope = Environment::new(HashMap::new(), Some(environment));
+            frames.push(Resume::Block(statements, 0, Rc::new(RefCell::new(scope))));
+            Ok(Step::Next)
+        }
+        Stmt::If(keyword, condition, then_branch, else_branch) => {
+            let value = interpreter.with_environment(environment.clone(), |interpreter| {
+                interpreter.evaluate_expression(*condition)
+            })?;
+            let branch = if interpreter.is_truthy(value) {
+                interpreter.branch(&keyword, 0);
+                Some(*then_branch)
+            } else {
+                interpreter.branch(&keyword, 1);
+                *else_branch
+            };
+            if let Some(branch) = branch {
+                frames.push(Resume::Block(vec![branch], 0, environment));
+            }
+            Ok(Step::Next)
+        }
+        Stmt::While(keyword, condition, body) => {
+            frames.push(Resume::While {
+                keyword,
+                condition: *condition,
+                body: *body,
+                environment,
+                started: false,
+            });
+            Ok(Step::Next)
+        }
+        Stmt::ForIn(keyword, name, iterable, body) => {
+            let iterable = interpreter.with_environment(environment.clone(), |interpreter| {
+                interpreter.evaluate_expression(*iterable)
+            })?;
+            let iteration = interpreter.iteration(&keyword, iterable)?;
+            frames.push(Resume::ForIn {
+                keyword,
+                name: *name,
+                body: *body,
+                iteration,
+                environment,
+                started: false,
+            });
+            Ok(Step::Next)
+        }
+        _ => unreachable!("only statements that pause are taken apart: {}", stmt),
+    }
+}
+
+// whether statements yield, which makes the function they're the body of a
+// generator; functions and classes declared in them yield for themselves
+pub fn yields(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| pauses(stmt, false))
+}
+
+// whether the body can stop somewhere in a statement, so it has to be taken apart
+fn pauses(stmt: &Stmt, task: bool) -> bool {
+    match stmt {
+        Stmt::Yield(_, _) => true,
+        Stmt::Block(_, stmts) => stmts.iter().any(|stmt| pauses(stmt, task)),
+        Stmt::If(_, _, then_branch, else_branch) => {
+            pauses(then_branch, task)
+                || (**else_branch)
+                    .as_ref()
+                    .is_some_and(|else_branch| pauses(else_branch, task))
+        }
+        Stmt::While(_, _, body) | Stmt::ForIn(_, _, _, body) => pauses(body, task),
+        _ => task && (awaits(stmt).is_some() || calls(stmt).is_some()),
+    }
+}
+
+// whether a task can stop somewhere in statements, at an await or in a call
+pub fn suspends(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| pauses(stmt, true))
+}
+
+// the await a task can stop at in a statement, with what becomes of its value
+fn awaits(stmt: &Stmt) -> Option<(&Token, &Expr, Action)> {
+    match settled(stmt)? {
+        (Expr::Await(keyword, awaited), action) => Some((keyword, awaited, action)),
+        _ => None,
+    }
+}
+
+// the call a task steps into in a statement, as the function could await
+fn calls(stmt: &Stmt) -> Option<(&Expr, &Token, &Vec<Expr>, Action)> {
+    match settled(stmt)? {
+        (Expr::Call(callee, paren, arguments), action) => Some((callee, paren, arguments, action)),
+        _ => None,
+    }
+}
+
+// the expression whose value a statement waits on, and what becomes of the value
+fn settled(stmt: &Stmt) -> Option<(&Expr, Action)> {
+    match stmt {
+        Stmt::Expression(_, expr) => Some((expr, Action::Discard)),
+        Stmt::VariableDeclaration(name, _, initializer) => {
+            Some((initializer, Action::Define(*name.clone())))
+        }
+        Stmt::Return(_, value) => Some((value, Action::Return)),
+        _ => None,
+    }
+}
+
+impl Display for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<generator {}>", self.name.lexeme)
+    }
+}
+
+impl Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(generator {})", self.name.lexeme)
+    }
+}
+
+// every call gives a generator of its own
+impl PartialEq for Generator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
@@ -1,44 +1,59 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
 
-use super::{class::Class, value::Value};
+use super::{class::Class, interpreter::Interpreter, value::Value};
 
-#[derive(Clone, Debug, PartialEq)]
+// clones share their fields, so every copy of a value refers to the same instance
+#[derive(Clone, Debug)]
 pub struct Instance {
     klass: Class,
-    fields: HashMap<String, Value>,
+    fields: Rc<RefCell<HashMap<String, Value>>>,
 }
 
 impl Instance {
     pub fn new(klass: Class) -> Self {
         Self {
             klass,
-            fields: HashMap::new(),
+            fields: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
-    pub fn get(&self, name: String) -> Result<Value, String> {
-        let value = self.fields.get(&name);
-        match value {
-            Some(result) => Ok(result.clone()),
-            None => match self.klass.find_method(&name) {
-                Some(method) => method.bind(self),
-                None => Err(format!("Undefined property '{}'", name)),
-            },
+    // fields shadow getters, which shadow methods
+    pub fn get(&self, name: String, interpreter: &mut Interpreter) -> Result<Value, String> {
+        let value = self.fields.borrow().get(&name).cloned();
+        if let Some(result) = value {
+            return Ok(result);
+        }
+        if let Some(getter) = self.klass.find_getter(&name) {
+            return getter.bind(self)?.call(interpreter, Vec::new());
+        }
+        match self.klass.find_method(&name) {
+            Some(method) => method.bind(self),
+            None => Err(format!("Undefined property '{}'", name)),
         }
     }
 
-    pub fn set(&mut self, name: String, value: Value) {
-        self.fields.insert(name, value);
+    pub fn set(&self, name: String, value: Value) {
+        self.fields.borrow_mut().insert(name, value);
+    }
+
+    pub fn class(&self) -> &Class {
+        &self.klass
+    }
+
+    pub fn fields(&self) -> HashMap<String, Value> {
+        self.fields.borrow().clone()
+    }
+}
+
+// instances are equal only to themselves
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
     }
 }
 
 impl Display for Instance {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "(instance {}, {:?})",
-            self.klass,
-            self.fields.keys().collect::<Vec<&String>>()
-        )
+        write!(f, "{} instance", self.klass)
     }
 }
@@ -1,99 +1,494 @@
 use std::{
-    array::IntoIter,
     cell::RefCell,
     collections::HashMap,
+    convert::TryFrom,
+    io::{self, Write},
     iter::FromIterator,
     rc::Rc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use super::{
-    callable::{Function, NativeFunction},
-    class::Class,
+    callable::{describe_arities, Function, NativeFunction},
+    class::{Class, Members, Trait},
     environment::Environment,
     expr::Expr,
-    stmt::Stmt,
+    generator::{Generator, Outcome},
+    stmt::{MethodKind, Signature, Stmt},
+    task::{Channel, Scheduler, Task},
     token::Token,
     token_type::TokenType,
-    value::Value,
+    value::{Range, Value},
 };
 
 pub struct Interpreter {
     pub globals: Rc<RefCell<Environment>>,
     environment: Rc<RefCell<Environment>>,
     locals: Rc<RefCell<HashMap<Expr, usize>>>,
+    // one per active function call, the script itself is at the bottom
+    pub frames: Vec<Frame>,
+    hook: Option<Box<dyn Hook>>,
+    // where print writes, stdout unless a tool wants to capture it
+    output: Box<dyn Write>,
+    // the spawned tasks, run whenever something awaits and once the script has finished
+    scheduler: Scheduler,
+}
+
+// how a statement can end early, both travel up through the enclosing statements
+pub enum Unwind {
+    Return(Value),
+    Error(String),
+    // the resolver keeps these inside loops, which stop them
+    Break,
+    Continue,
+}
+
+impl From<String> for Unwind {
+    fn from(error: String) -> Self {
+        Self::Error(error)
+    }
+}
+
+// where a for-in loop gets its next value from
+pub enum Iteration {
+    Values(Box<dyn Iterator<Item = Value>>),
+    Generator(Generator),
+    // an object with 'hasNext' and 'next' methods
+    Iterator(Box<Value>),
+}
+
+impl Iteration {
+    pub fn next(
+        &mut self,
+        interpreter: &mut Interpreter,
+        keyword: &Token,
+    ) -> Result<Option<Value>, String> {
+        let iterator = match self {
+            Iteration::Values(values) => return Ok(values.next()),
+            Iteration::Generator(generator) => {
+                return generator
+                    .next(interpreter)
+                    .map_err(|err| with_line(keyword, err))
+            }
+            Iteration::Iterator(iterator) => (**iterator).clone(),
+        };
+        let missing = || {
+            runtime_error(
+                keyword,
+                format!(
+                    "Iterator must have 'hasNext' and 'next' methods, got {}",
+                    iterator.describe_type()
+                ),
+            )
+        };
+        let has_next = interpreter
+            .call_special(&iterator, "hasNext", Vec::new())
+            .ok_or_else(missing)?
+            .map_err(|err| with_line(keyword, err))?;
+        if !interpreter.is_truthy(has_next) {
+            return Ok(None);
+        }
+        interpreter
+            .call_special(&iterator, "next", Vec::new())
+            .ok_or_else(missing)?
+            .map(Some)
+            .map_err(|err| with_line(keyword, err))
+    }
+}
+
+pub struct Frame {
+    pub name: String,
+    // line of the statement being run in this frame
+    pub line: i32,
+    // innermost environment of this frame as of its current statement
+    pub environment: Rc<RefCell<Environment>>,
+}
+
+// lets tools like the debugger watch execution
+pub trait Hook {
+    // called before every statement except blocks, with the top frame up to date
+    fn before_statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt);
+
+    // called once the statement is done, even if it returned or failed
+    fn after_statement(&mut self, _interpreter: &mut Interpreter, _stmt: &Stmt) {}
+
+    // called with the callee's frame on top
+    fn enter_call(&mut self, _interpreter: &mut Interpreter) {}
+
+    // called before the callee's frame is popped
+    fn exit_call(&mut self, _interpreter: &mut Interpreter) {}
+
+    // called when an if, while or logical operator picks a way to go, the token is
+    // its keyword or operator; arm 0 is the then branch, entering the loop body or
+    // short-circuiting, arm 1 is the else branch, skipping the body or evaluating the right side
+    fn branch(&mut self, _interpreter: &mut Interpreter, _token: &Token, _arm: usize) {}
+}
+
+// lets a tool keep a handle on its hook to read the results afterwards
+impl<T: Hook> Hook for Rc<RefCell<T>> {
+    fn before_statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) {
+        self.borrow_mut().before_statement(interpreter, stmt)
+    }
+
+    fn after_statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) {
+        self.borrow_mut().after_statement(interpreter, stmt)
+    }
+
+    fn enter_call(&mut self, interpreter: &mut Interpreter) {
+        self.borrow_mut().enter_call(interpreter)
+    }
+
+    fn exit_call(&mut self, interpreter: &mut Interpreter) {
+        self.borrow_mut().exit_call(interpreter)
+    }
+
+    fn branch(&mut self, interpreter: &mut Interpreter, token: &Token, arm: usize) {
+        self.borrow_mut().branch(interpreter, token, arm)
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let env = Rc::from(RefCell::from(Environment::new(
-            HashMap::from_iter(IntoIter::new([(
-                "clock".to_string(),
-                Value::NativeFunction(NativeFunction::new("clock".to_string(), 0, |_, _| {
-                    let start = SystemTime::now();
-                    let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-
-                    Ok(Value::Number(since_the_epoch.as_millis() as f64))
-                })),
-            )])),
+            HashMap::from_iter(IntoIterator::into_iter([
+                (
+                    "clock".to_string(),
+                    Value::NativeFunction(NativeFunction::new("clock".to_string(), 0, |_, _| {
+                        let start = SystemTime::now();
+                        let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
+
+                        Ok(Value::Int(since_the_epoch.as_millis() as i64))
+                    })),
+                ),
+                (
+                    "assert".to_string(),
+                    Value::NativeFunction(NativeFunction::new(
+                        "assert".to_string(),
+                        2,
+                        |interpreter, arguments| {
+                            if interpreter.is_truthy(arguments[0].clone()) {
+                                Ok(Value::Nil)
+                            } else {
+                                Err(format!("Assertion failed: {}", arguments[1]))
+                            }
+                        },
+                    )),
+                ),
+                (
+                    // instanceof(value, class), the same as "value is class"
+                    "instanceof".to_string(),
+                    Value::NativeFunction(NativeFunction::new(
+                        "instanceof".to_string(),
+                        2,
+                        |_, arguments| match &arguments[1] {
+                            Value::Class(class) => {
+                                Ok(Value::from(arguments[0].is_instance_of(class)))
+                            }
+                            class => Err(format!(
+                                "Second argument of 'instanceof' must be a class, got {}",
+                                class.describe_type()
+                            )),
+                        },
+                    )),
+                ),
+                (
+                    // range(start, end, step), for for-in loops; step is 1 when left out
+                    "range".to_string(),
+                    Value::NativeFunction(
+                        NativeFunction::new("range".to_string(), 3, |_, arguments| {
+                            let mut bounds = Vec::new();
+                            for argument in &arguments {
+                                match as_integer(argument) {
+                                    Some(bound) => bounds.push(bound),
+                                    None => {
+                                        return Err(format!(
+                                            "Arguments of 'range' must be integers, got {}",
+                                            describe_operand(argument)
+                                        ))
+                                    }
+                                }
+                            }
+                            let step = bounds.get(2).copied().unwrap_or(1);
+                            if step == 0 {
+                                return Err("Step of 'range' can't be zero".to_string());
+                            }
+                            Ok(Value::Range(Range {
+                                start: bounds[0],
+                                end: bounds[1],
+                                step,
+                            }))
+                        })
+                        .with_optional(1),
+                    ),
+                ),
+                (
+                    // sleep(ms), a task finishing once that much time has been slept
+                    "sleep".to_string(),
+                    Value::NativeFunction(NativeFunction::new(
+                        "sleep".to_string(),
+                        1,
+                        |interpreter, arguments| {
+                            match as_integer(&arguments[0]) {
+                            Some(milliseconds) if milliseconds >= 0 => {
+                                Ok(Value::Task(interpreter.scheduler.sleep(milliseconds)?))
+                            }
+                            _ => Err(format!(
+                                "Argument of 'sleep' must be a whole number of milliseconds, got {}",
+                                describe_operand(&arguments[0])
+                            )),
+                        }
+                        },
+                    )),
+                ),
+                (
+                    // channel(), for tasks to pass values to each other
+                    "channel".to_string(),
+                    Value::NativeFunction(NativeFunction::new("channel".to_string(), 0, |_, _| {
+                        Ok(Value::Channel(Channel::default()))
+                    })),
+                ),
+                (
+                    // classOf(value), nil for anything that isn't an instance
+                    "classOf".to_string(),
+                    Value::NativeFunction(NativeFunction::new(
+                        "classOf".to_string(),
+                        1,
+                        |_, arguments| match &arguments[0] {
+                            Value::Instance(instance) => Ok(Value::Class(instance.class().clone())),
+                            _ => Ok(Value::Nil),
+                        },
+                    )),
+                ),
+                (
+                    // assert_eq(actual, expected)
+                    "assert_eq".to_string(),
+                    Value::NativeFunction(NativeFunction::new(
+                        "assert_eq".to_string(),
+                        2,
+                        |interpreter, arguments| {
+                            if interpreter.is_equal(arguments[0].clone(), arguments[1].clone()) {
+                                Ok(Value::Nil)
+                            } else {
+                                Err(format!(
+                                    "Assertion failed: expected {} but got {}",
+                                    arguments[1], arguments[0]
+                                ))
+                            }
+                        },
+                    )),
+                ),
+            ])),
             None,
         )));
         Self {
             environment: env.clone(),
+            frames: vec![Frame {
+                name: "script".to_string(),
+                line: 0,
+                environment: env.clone(),
+            }],
             globals: env,
             locals: Rc::from(RefCell::from(HashMap::new())),
+            hook: None,
+            output: Box::new(io::stdout()),
+            scheduler: Scheduler::default(),
         }
     }
 
-    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), Value> {
+    pub fn scheduler(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    pub fn set_hook(&mut self, hook: Box<dyn Hook>) {
+        self.hook = Some(hook);
+    }
+
+    pub fn environment(&self) -> Rc<RefCell<Environment>> {
+        self.environment.clone()
+    }
+
+    pub fn push_frame(&mut self, name: String, line: i32) {
+        if let Some(caller) = self.frames.last_mut() {
+            caller.environment = self.environment.clone();
+        }
+        self.frames.push(Frame {
+            name,
+            line,
+            environment: self.environment.clone(),
+        });
+        self.with_hook(|hook, interpreter| hook.enter_call(interpreter));
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.with_hook(|hook, interpreter| hook.exit_call(interpreter));
+        self.frames.pop();
+    }
+
+    // the hook is taken out while it runs, so code it evaluates doesn't re-enter it
+    fn with_hook<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut dyn Hook, &mut Interpreter),
+    {
+        if let Some(mut hook) = self.hook.take() {
+            f(hook.as_mut(), self);
+            self.hook = Some(hook);
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), String> {
         for stmt in stmts {
-            self.execute(stmt)?;
+            // the resolver rejects top level returns, so only errors get here
+            if let Err(Unwind::Error(err)) = self.execute(stmt) {
+                return Err(err);
+            }
+        }
+        // tasks nothing awaited still get to run
+        self.run_tasks(None)
+    }
+
+    // runs tasks in the scheduler's order until the awaited one has finished, or with
+    // none awaited until none can go on
+    fn run_tasks(&mut self, awaited: Option<&Task>) -> Result<(), String> {
+        while awaited.is_none_or(|awaited| awaited.result().is_none()) {
+            let (task, sent) = match self.scheduler.next() {
+                Some(next) => next,
+                None => break,
+            };
+            let body = match task.body() {
+                Some(body) => body,
+                None => continue,
+            };
+            self.scheduler.running = true;
+            let outcome = body.resume(self, sent);
+            self.scheduler.running = false;
+            match outcome? {
+                Outcome::Awaiting(on) => self.scheduler.wait(task, &on),
+                Outcome::Finished(value) => self.scheduler.finish(&task, value),
+                Outcome::Yielded(_) => unreachable!("a task's body errors rather than yield"),
+            }
         }
         Ok(())
     }
 
-    fn execute(&mut self, stmt: Stmt) -> Result<(), Value> {
+    // evaluates a lone expression, used by the REPL to echo results
+    pub fn evaluate_expression(&mut self, expr: Expr) -> Result<Value, String> {
+        self.evaluate(expr)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn execute(&mut self, stmt: Stmt) -> Result<(), Unwind> {
+        if matches!(stmt, Stmt::Block(_, _)) {
+            return self.execute_statement(stmt);
+        }
+        self.before_statement(&stmt);
+        if self.hook.is_none() {
+            return self.execute_statement(stmt);
+        }
+        let result = self.execute_statement(stmt.clone());
+        self.with_hook(|hook, interpreter| hook.after_statement(interpreter, &stmt));
+        result
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn execute_statement(&mut self, stmt: Stmt) -> Result<(), Unwind> {
         match stmt.clone() {
-            Stmt::Expression(expr) => {
+            Stmt::Expression(_, expr) => {
                 self.evaluate(*expr)?;
             }
-            Stmt::Print(expr) => {
-                println!("{}", self.evaluate(*expr)?.to_string());
+            Stmt::Print(keyword, expr) => {
+                let value = self.evaluate(*expr)?;
+                let text = self
+                    .stringify(value)
+                    .map_err(|err| with_line(&keyword, err))?;
+                writeln!(self.output, "{}", text).map_err(|err| err.to_string())?;
             }
-            Stmt::VariableDeclaration(name, initializer) => {
+            Stmt::VariableDeclaration(name, _, initializer) => {
                 let eval = self.evaluate(*initializer)?;
                 self.environment.borrow_mut().define(name.lexeme, eval);
             }
-            Stmt::Block(statements) => {
+            Stmt::Block(_, statements) => {
                 let new_environment =
                     Environment::new(HashMap::new(), Some(self.environment.clone()));
                 self.execute_block(statements, new_environment)?;
             }
-            Stmt::If(condition, then_branch, maybe_else_branch) => {
+            Stmt::If(keyword, condition, then_branch, maybe_else_branch) => {
                 let eval = self.evaluate(*condition)?;
                 if self.is_truthy(eval) {
+                    self.branch(&keyword, 0);
                     self.execute(*then_branch)?;
-                } else if let Some(else_branch) = *maybe_else_branch {
-                    self.execute(else_branch)?;
+                } else {
+                    self.branch(&keyword, 1);
+                    if let Some(else_branch) = *maybe_else_branch {
+                        self.execute(else_branch)?;
+                    }
                 }
             }
-            Stmt::While(condition, body) => {
+            Stmt::While(keyword, condition, body) => {
                 let mut evaluation = self.evaluate(*condition.clone())?;
+                let entered = self.is_truthy(evaluation.clone());
+                self.branch(&keyword, if entered { 0 } else { 1 });
                 while self.is_truthy(evaluation) {
-                    self.execute(*body.clone())?;
+                    match self.execute(*body.clone()) {
+                        Ok(()) | Err(Unwind::Continue) => (),
+                        Err(Unwind::Break) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
                     evaluation = self.evaluate(*condition.clone())?;
                 }
             }
-            Stmt::ClassDeclaration(name, superclass, methods) => {
+            Stmt::ForIn(keyword, name, iterable, body) => {
+                let iterable = self.evaluate(*iterable)?;
+                let mut iteration = self.iteration(&keyword, iterable)?;
+                let mut next = iteration.next(self, &keyword)?;
+                self.branch(&keyword, if next.is_some() { 0 } else { 1 });
+                while let Some(value) = next {
+                    // a fresh environment each time, so closures keep the value they saw
+                    let mut environment =
+                        Environment::new(HashMap::new(), Some(self.environment.clone()));
+                    environment.define(name.lexeme.clone(), value);
+                    match self.execute_block(vec![*body.clone()], environment) {
+                        Ok(()) | Err(Unwind::Continue) => (),
+                        Err(Unwind::Break) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
+                    next = iteration.next(self, &keyword)?;
+                }
+            }
+            Stmt::ClassDeclaration(name, superclass, trait_names, methods) => {
                 let mut superklass = None;
                 let original_environment = self.environment.clone();
                 if let Some(superclass_expr) = superclass.clone() {
-                    let superclass_eval = self.evaluate(superclass_expr)?;
+                    let superclass_eval = self.evaluate(superclass_expr.clone())?;
                     match superclass_eval {
                         Value::Class(superklass_object) => {
                             superklass = Some(Box::from(superklass_object))
                         }
-                        _ => return Err(Value::Nil),
+                        _ => {
+                            return Err(Unwind::from(runtime_error(
+                                &superclass_name(&superclass_expr),
+                                format!(
+                                    "Superclass must be a class, got {}",
+                                    superclass_eval.describe_type()
+                                ),
+                            )))
+                        }
+                    }
+                }
+                let mut traits = Vec::new();
+                for trait_name in trait_names {
+                    match self.evaluate(trait_name.clone())? {
+                        Value::Trait(mixed) => traits.push(mixed),
+                        value => {
+                            return Err(Unwind::from(runtime_error(
+                                &superclass_name(&trait_name),
+                                format!("Can only mix in traits, got {}", value.describe_type()),
+                            )))
+                        }
                     }
                 }
                 self.environment
@@ -110,30 +505,23 @@ impl Interpreter {
                         .borrow_mut()
                         .define("super".to_string(), superclass_eval);
                 }
-                let mut methods_map = HashMap::new();
-                for method in methods {
-                    if let Stmt::FunctionDeclaration(name, _, _) = &method {
-                        methods_map.insert(
-                            name.lexeme.clone(),
-                            Value::Function(Function::new(
-                                method.clone(),
-                                self.environment.clone(),
-                                name.lexeme.clone() == "init".to_string(),
-                            )),
-                        );
-                    }
-                }
-                let klass = Class::new(name.lexeme.clone(), superklass, methods_map);
-
-                if let Some(_) = superclass.clone() {
-                    self.environment = original_environment;
-                }
+                let mut members = self.members(&methods);
+                let mixed = members.mix_in(&name.lexeme, &traits);
+                self.environment = original_environment;
+                mixed.map_err(|err| runtime_error(&name, err))?;
+                let klass = Class::new(name.lexeme.clone(), superklass, members);
 
                 self.environment
                     .borrow_mut()
                     .assign(name.lexeme, Value::Class(klass))?;
             }
-            Stmt::FunctionDeclaration(name, _, _) => {
+            Stmt::TraitDeclaration(name, methods) => {
+                let mixed = Trait::new(name.lexeme.clone(), self.members(&methods));
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme, Value::Trait(mixed));
+            }
+            Stmt::FunctionDeclaration(name, _, _, _) => {
                 let function =
                     Value::Function(Function::new(stmt, self.environment.clone(), false));
                 self.environment.borrow_mut().define(name.lexeme, function);
@@ -143,26 +531,64 @@ impl Interpreter {
                 if *value != Expr::NilLiteral {
                     return_value = self.evaluate(*value)?;
                 };
-                return Err(return_value);
+                return Err(Unwind::Return(return_value));
+            }
+            Stmt::Break(_) => return Err(Unwind::Break),
+            Stmt::Continue(_) => return Err(Unwind::Continue),
+            // generators take their yields apart themselves, see Generator
+            Stmt::Yield(keyword, _) => {
+                return Err(Unwind::from(runtime_error(
+                    &keyword,
+                    "Can only yield from a generator".to_string(),
+                )))
             }
         };
         Ok(())
     }
 
+    pub fn before_statement(&mut self, stmt: &Stmt) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = stmt.line();
+            frame.environment = self.environment.clone();
+        }
+        self.with_hook(|hook, interpreter| hook.before_statement(interpreter, stmt));
+    }
+
+    // runs f with the environment swapped out, for generators picking up where they left off
+    pub fn with_environment<T, F>(&mut self, environment: Rc<RefCell<Environment>>, f: F) -> T
+    where
+        F: FnOnce(&mut Interpreter) -> T,
+    {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = f(self);
+        self.environment = previous;
+        result
+    }
+
+    pub fn branch(&mut self, token: &Token, arm: usize) {
+        self.with_hook(|hook, interpreter| hook.branch(interpreter, token, arm));
+    }
+
+    #[allow(clippy::result_large_err)]
     pub fn execute_block(
         &mut self,
         statements: Vec<Stmt>,
         new_environment: Environment,
-    ) -> Result<(), Value> {
+    ) -> Result<(), Unwind> {
         // set current environment to newly constructed environment
         let previous = self.environment.clone();
         self.environment = Rc::from(RefCell::from(new_environment));
 
+        // a desugared for loop's increment still runs when its body continues
+        let increment = for_increment(&statements).cloned();
         for statement in statements {
-            let result = self.execute(statement);
-            if let Err(value) = result {
+            let mut result = self.execute(statement);
+            if let (Err(Unwind::Continue), Some(increment)) = (&result, increment.clone()) {
+                result = self.execute(increment).and(Err(Unwind::Continue));
+            }
+            if let Err(unwind) = result {
                 self.environment = previous;
-                return Err(value);
+                return Err(unwind);
             }
         }
 
@@ -186,68 +612,133 @@ impl Interpreter {
                     None => {
                         self.globals
                             .borrow_mut()
-                            .assign(name.lexeme, evaluated_value.clone())?;
+                            .assign(name.lexeme.clone(), evaluated_value.clone())
+                            .map_err(|err| runtime_error(&name, err))?;
                     }
                 }
                 Ok(evaluated_value)
             }
+            Expr::CompoundAssign(..) | Expr::CompoundSet(..) => {
+                self.update(&expr).map(|(_, updated)| updated)
+            }
+            Expr::Postfix(update, _) => self.update(&update).map(|(original, _)| original),
             Expr::Binary(left, operator, right) => self.binary(*left, operator, *right),
             Expr::Grouping(group_expr) => self.evaluate(*group_expr),
             Expr::Urnary(operator, right) => self.urnary(operator, *right),
             Expr::StringLiteral(string_literal) => {
                 Ok(Value::from(string_literal.clone().to_string()))
             }
-            Expr::NumberLiteral(number_literal) => Ok(Value::from(number_literal)),
+            Expr::NumberLiteral(number_literal, _) => Ok(Value::from(number_literal)),
+            Expr::IntLiteral(int_literal, _) => Ok(Value::from(int_literal)),
             Expr::NilLiteral => Ok(Value::Nil),
             Expr::TrueLiteral => Ok(Value::Bool(true)),
             Expr::FalseLiteral => Ok(Value::Bool(false)),
             Expr::Variable(token) => Ok(self.lookup_variable(token, &expr)?),
             Expr::Logical(left, operator, right) => {
                 let left_value = self.evaluate(*left)?;
-                if TokenType::Or == operator.token_type && self.is_truthy(left_value.clone()) {
-                    Ok(left_value)
-                } else if TokenType::And == operator.token_type
-                    && !self.is_truthy(left_value.clone())
+                if (TokenType::Or == operator.token_type && self.is_truthy(left_value.clone()))
+                    || (TokenType::And == operator.token_type
+                        && !self.is_truthy(left_value.clone()))
+                    || (TokenType::QuestionQuestion == operator.token_type
+                        && left_value != Value::Nil)
                 {
+                    self.branch(&operator, 0);
                     Ok(left_value)
                 } else {
+                    self.branch(&operator, 1);
                     self.evaluate(*right)
                 }
             }
-            Expr::Call(callee, _, args) => {
+            // "a?.b" ends the whole chain it starts when a is nil, see link
+            Expr::Call(..) | Expr::Get(..) | Expr::OptionalGet(..) | Expr::Index(..) => {
+                Ok(self.link(expr)?.unwrap_or(Value::Nil))
+            }
+            Expr::Await(keyword, task) => {
+                let task = match self.evaluate(*task)? {
+                    Value::Task(task) => task,
+                    other => {
+                        return Err(runtime_error(
+                            &keyword,
+                            format!("Can only await tasks, got {}", other.describe_type()),
+                        ))
+                    }
+                };
+                if let Some(value) = task.result() {
+                    return Ok(value);
+                }
+                // a task can only stop where it can pick up again, which it can't in
+                // a call nested in some other expression, see Generator
+                if self.scheduler.running {
+                    return Err(runtime_error(
+                        &keyword,
+                        "A task can only await in calls that are a statement of their own, a variable's initializer or a return".to_string(),
+                    ));
+                }
+                self.run_tasks(Some(&task))?;
+                task.result().ok_or_else(|| {
+                    runtime_error(&keyword, format!("Task '{}' can never finish", task.name()))
+                })
+            }
+            Expr::Spawn(keyword, call) => {
+                let (callee, paren, arguments) = match *call {
+                    Expr::Call(callee, paren, arguments) => (callee, paren, arguments),
+                    other => {
+                        return Err(runtime_error(
+                            &keyword,
+                            format!("Expected a call after 'spawn': {}", other),
+                        ))
+                    }
+                };
                 let callee = self.evaluate(*callee)?;
-                let mut arguments = Vec::new();
-                for arg in args {
-                    arguments.push(self.evaluate(arg)?);
+                let mut values = Vec::new();
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
                 }
-                callee.call(self, arguments)
+                let function = match callee {
+                    Value::Function(function) => function,
+                    other => {
+                        return Err(runtime_error(
+                            &paren,
+                            format!("Can only spawn functions, got {}", other.describe_type()),
+                        ))
+                    }
+                };
+                let (name, body) = function
+                    .task(values)
+                    .map_err(|err| runtime_error(&paren, err))?;
+                let task = Task::new(name, Some(body));
+                self.scheduler.spawn(task.clone());
+                Ok(Value::Task(task))
             }
-            Expr::Get(object, name) => {
-                let object = self.evaluate(*object)?;
-                if let Value::Instance(instance) = object {
-                    instance.get(name.lexeme)
+            Expr::Conditional(condition, question, then_branch, else_branch) => {
+                let condition = self.evaluate(*condition)?;
+                if self.is_truthy(condition) {
+                    self.branch(&question, 0);
+                    self.evaluate(*then_branch)
                 } else {
-                    Err("Only instances have properties".to_string())
+                    self.branch(&question, 1);
+                    self.evaluate(*else_branch)
                 }
             }
             Expr::Set(object, name, value) => {
                 let evaluated_object = self.evaluate(*object.clone())?;
                 match evaluated_object {
-                    Value::Instance(mut instance) => {
+                    Value::Instance(_) | Value::Class(_) => {
                         let value = self.evaluate(*value)?;
-                        instance.set(name.lexeme.clone(), value.clone());
-                        if let Expr::Variable(object_name) = *object {
-                            self.environment
-                                .borrow_mut()
-                                .assign(object_name.lexeme, Value::Instance(instance.clone()))?;
-                        }
+                        self.set_property(evaluated_object, name, value.clone())?;
                         Ok(value)
                     }
-                    _ => Err("Only instances have fields".to_string()),
+                    _ => Err(runtime_error(
+                        &name,
+                        format!(
+                            "Only instances have fields, got {}",
+                            evaluated_object.describe_type()
+                        ),
+                    )),
                 }
             }
             Expr::This(keyword) => self.lookup_variable(keyword, &expr),
-            Expr::Super(_, method) => {
+            Expr::Super(keyword, method) => {
                 let distance = *self.locals.borrow().get(&expr).unwrap();
                 let superclass = self
                     .environment
@@ -257,17 +748,23 @@ impl Interpreter {
                     .environment
                     .borrow()
                     .get_at(distance - 1, "this".to_string())?;
-                if let Value::Class(klass) = superclass {
-                    if let Value::Instance(instance) = object {
+                match (superclass, object) {
+                    (Value::Class(klass), Value::Instance(instance)) => {
                         match klass.find_method(&method.lexeme) {
                             Some(klass_method) => klass_method.bind(&instance),
-                            None => Err(format!("Undefined property {}", method.lexeme)),
+                            None => Err(runtime_error(
+                                &method,
+                                format!(
+                                    "Undefined method '{}' on superclass {}",
+                                    method.lexeme, klass
+                                ),
+                            )),
                         }
-                    } else {
-                        Err("Something went wrong".to_string())
                     }
-                } else {
-                    Err("Something went wrong".to_string())
+                    _ => Err(runtime_error(
+                        &keyword,
+                        "'super' is not bound to a class and instance".to_string(),
+                    )),
                 }
             }
         }
@@ -278,75 +775,238 @@ impl Interpreter {
         match operator.token_type {
             TokenType::Minus => match right_value {
                 Value::Number(right_number_value) => Ok(Value::from(-right_number_value)),
-                _ => Err("unexpected value".to_string()),
+                Value::Int(right_int_value) => match right_int_value.checked_neg() {
+                    Some(negated) => Ok(Value::from(negated)),
+                    None => Err(runtime_error(
+                        &operator,
+                        format!("Integer overflow in -{}", right_int_value),
+                    )),
+                },
+                _ => Err(runtime_error(
+                    &operator,
+                    format!(
+                        "Operand of '-' must be a number, got {}",
+                        right_value.describe_type()
+                    ),
+                )),
             },
             TokenType::Bang => Ok(Value::from(!self.is_truthy(right_value))),
-            _ => Err("unexpected token".to_string()),
+            TokenType::Tilde => match as_integer(&right_value) {
+                Some(right_integer_value) => Ok(Value::from(!right_integer_value)),
+                None => Err(runtime_error(
+                    &operator,
+                    format!(
+                        "Operand of '~' must be an integer, got {}",
+                        describe_operand(&right_value)
+                    ),
+                )),
+            },
+            _ => Err(runtime_error(
+                &operator,
+                format!("Unknown unary operator '{}'", operator.lexeme),
+            )),
         }
     }
 
     fn binary(&mut self, left: Expr, operator: Token, right: Expr) -> Result<Value, String> {
         let left_value = self.evaluate(left)?;
         let right_value = self.evaluate(right)?;
-        match operator.token_type {
-            TokenType::Greater => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value > right_number_value))
-                }
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::GreaterEqual => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value >= right_number_value))
-                }
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::Less => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value < right_number_value))
-                }
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::LessEqual => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value <= right_number_value))
+        self.apply_binary(left_value, operator, right_value)
+    }
+
+    fn apply_binary(
+        &mut self,
+        left_value: Value,
+        operator: Token,
+        right_value: Value,
+    ) -> Result<Value, String> {
+        if let Some(result) = self.overloaded(&left_value, &operator, &right_value) {
+            return result.map_err(|err| with_line(&operator, err));
+        }
+        match (operator.token_type, &left_value, &right_value) {
+            (TokenType::BangEqual, _, _) => {
+                Ok(Value::from(!self.is_equal(left_value, right_value)))
+            }
+            (TokenType::EqualEqual, _, _) => {
+                Ok(Value::from(self.is_equal(left_value, right_value)))
+            }
+            (TokenType::Is, _, Value::Class(class)) => {
+                Ok(Value::from(left_value.is_instance_of(class)))
+            }
+            (TokenType::Is, _, _) => Err(runtime_error(
+                &operator,
+                format!(
+                    "Right operand of 'is' must be a class, got {}",
+                    right_value.describe_type()
+                ),
+            )),
+            (
+                TokenType::Plus,
+                Value::String(left_string_value),
+                Value::String(right_string_value),
+            ) => Ok(Value::from(
+                [left_string_value.clone(), right_string_value.clone()].join(""),
+            )),
+            (
+                TokenType::Ampersand
+                | TokenType::Pipe
+                | TokenType::Caret
+                | TokenType::LessLess
+                | TokenType::GreaterGreater
+                | TokenType::Backslash,
+                _,
+                _,
+            ) => self.integer_binary(operator, &left_value, &right_value),
+            (_, Value::Int(left_int_value), Value::Int(right_int_value)) => {
+                self.int_binary(operator, *left_int_value, *right_int_value)
+            }
+            // an int meeting a float is promoted to a float
+            (_, left, right) if as_float(left).is_some() && as_float(right).is_some() => {
+                let (left_number_value, right_number_value) =
+                    (as_float(left).unwrap(), as_float(right).unwrap());
+                match operator.token_type {
+                    TokenType::Greater => Ok(Value::from(left_number_value > right_number_value)),
+                    TokenType::GreaterEqual => {
+                        Ok(Value::from(left_number_value >= right_number_value))
+                    }
+                    TokenType::Less => Ok(Value::from(left_number_value < right_number_value)),
+                    TokenType::LessEqual => {
+                        Ok(Value::from(left_number_value <= right_number_value))
+                    }
+                    TokenType::Plus => Ok(Value::from(left_number_value + right_number_value)),
+                    TokenType::Slash => Ok(Value::from(left_number_value / right_number_value)),
+                    TokenType::Star => Ok(Value::from(left_number_value * right_number_value)),
+                    TokenType::Minus => Ok(Value::from(left_number_value - right_number_value)),
+                    TokenType::Percent => Ok(Value::from(left_number_value % right_number_value)),
+                    TokenType::StarStar => {
+                        Ok(Value::from(left_number_value.powf(right_number_value)))
+                    }
+                    _ => Err(runtime_error(
+                        &operator,
+                        format!("Unknown binary operator '{}'", operator.lexeme),
+                    )),
                 }
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::Slash => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value / right_number_value))
+            }
+            (TokenType::Plus, _, _) => Err(runtime_error(
+                &operator,
+                format!(
+                    "Operands of '+' must be two numbers or two strings, got {} and {}",
+                    left_value.describe_type(),
+                    right_value.describe_type()
+                ),
+            )),
+            _ => Err(runtime_error(
+                &operator,
+                format!(
+                    "Operands of '{}' must be numbers, got {} and {}",
+                    operator.lexeme,
+                    left_value.describe_type(),
+                    right_value.describe_type()
+                ),
+            )),
+        }
+    }
+
+    // ints stay ints, except for "/" and negative powers, and overflowing is an error
+    fn int_binary(&self, operator: Token, left: i64, right: i64) -> Result<Value, String> {
+        let result = match operator.token_type {
+            TokenType::Greater => return Ok(Value::from(left > right)),
+            TokenType::GreaterEqual => return Ok(Value::from(left >= right)),
+            TokenType::Less => return Ok(Value::from(left < right)),
+            TokenType::LessEqual => return Ok(Value::from(left <= right)),
+            TokenType::Slash => return Ok(Value::from(left as f64 / right as f64)),
+            TokenType::Plus => left.checked_add(right),
+            TokenType::Minus => left.checked_sub(right),
+            TokenType::Star => left.checked_mul(right),
+            TokenType::Percent => {
+                if right == 0 {
+                    return Err(runtime_error(&operator, "Division by zero".to_string()));
                 }
-                _ => Err("unexpected value".to_string()),
+                // the remainder always fits, even where the quotient wouldn't
+                Some(left.wrapping_rem(right))
+            }
+            TokenType::StarStar => match u32::try_from(right) {
+                Ok(exponent) => left.checked_pow(exponent),
+                Err(_) if right < 0 => return Ok(Value::from((left as f64).powf(right as f64))),
+                Err(_) => None,
             },
-            TokenType::Star => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value * right_number_value))
+            _ => {
+                return Err(runtime_error(
+                    &operator,
+                    format!("Unknown binary operator '{}'", operator.lexeme),
+                ))
+            }
+        };
+        match result {
+            Some(result) => Ok(Value::from(result)),
+            None => Err(runtime_error(
+                &operator,
+                format!("Integer overflow in {} {} {}", left, operator.lexeme, right),
+            )),
+        }
+    }
+
+    // bitwise operators and "\" only make sense on whole numbers
+    fn integer_binary(
+        &self,
+        operator: Token,
+        left_value: &Value,
+        right_value: &Value,
+    ) -> Result<Value, String> {
+        let (left, right) = match (as_integer(left_value), as_integer(right_value)) {
+            (Some(left), Some(right)) => (left, right),
+            _ => {
+                return Err(runtime_error(
+                    &operator,
+                    format!(
+                        "Operands of '{}' must be integers, got {} and {}",
+                        operator.lexeme,
+                        describe_operand(left_value),
+                        describe_operand(right_value)
+                    ),
+                ))
+            }
+        };
+        let result = match operator.token_type {
+            TokenType::Ampersand => left & right,
+            TokenType::Pipe => left | right,
+            TokenType::Caret => left ^ right,
+            TokenType::LessLess | TokenType::GreaterGreater => {
+                if !(0..64).contains(&right) {
+                    return Err(runtime_error(
+                        &operator,
+                        format!("Shift amount must be between 0 and 63, got {}", right),
+                    ));
                 }
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::Minus => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value - right_number_value))
+                if operator.token_type == TokenType::LessLess {
+                    left << right
+                } else {
+                    left >> right
                 }
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::Plus => match (left_value, right_value) {
-                (Value::Number(left_number_value), Value::Number(right_number_value)) => {
-                    Ok(Value::from(left_number_value + right_number_value))
+            }
+            TokenType::Backslash => {
+                if right == 0 {
+                    return Err(runtime_error(&operator, "Division by zero".to_string()));
                 }
-                (Value::String(left_string_value), Value::String(right_string_value)) => Ok(
-                    Value::from([left_string_value, right_string_value].join("")),
-                ),
-                _ => Err("unexpected value".to_string()),
-            },
-            TokenType::BangEqual => Ok(Value::from(!self.is_equal(left_value, right_value))),
-            TokenType::EqualEqual => Ok(Value::from(self.is_equal(left_value, right_value))),
-            _ => Err("unexpected token".to_string()),
-        }
+                // rounds towards zero like "%", so (a \ b) * b + a % b is always a
+                left.checked_div(right).ok_or_else(|| {
+                    runtime_error(
+                        &operator,
+                        format!("Integer overflow in {} \\ {}", left, right),
+                    )
+                })?
+            }
+            _ => {
+                return Err(runtime_error(
+                    &operator,
+                    format!("Unknown binary operator '{}'", operator.lexeme),
+                ))
+            }
+        };
+        Ok(Value::from(result))
     }
 
-    fn is_truthy(&self, value: Value) -> bool {
+    pub fn is_truthy(&self, value: Value) -> bool {
         match value {
             Value::Nil => false,
             Value::Bool(value) => value,
@@ -362,10 +1022,396 @@ impl Interpreter {
         self.locals.borrow_mut().insert(expression.clone(), depth);
     }
 
+    pub fn forget(&self, expression: &Expr) {
+        self.locals.borrow_mut().remove(expression);
+    }
+
+    // runs a compound assignment, returning the value before and after it
+    fn update(&mut self, expr: &Expr) -> Result<(Value, Value), String> {
+        match expr.clone() {
+            Expr::CompoundAssign(name, operator, value) => {
+                let original = self.lookup_variable(name.clone(), expr)?;
+                let value = self.evaluate(*value)?;
+                let updated =
+                    self.apply_binary(original.clone(), operator.compound_operator(), value)?;
+                match self.locals.borrow().get(expr) {
+                    Some(distance) => {
+                        self.environment.borrow_mut().assign_at(
+                            *distance,
+                            name.lexeme,
+                            updated.clone(),
+                        );
+                    }
+                    None => {
+                        self.globals
+                            .borrow_mut()
+                            .assign(name.lexeme.clone(), updated.clone())
+                            .map_err(|err| runtime_error(&name, err))?;
+                    }
+                }
+                Ok((original, updated))
+            }
+            Expr::CompoundSet(object, name, operator, value) => {
+                let object = self.evaluate(*object)?;
+                let original = self.property(object.clone(), name.clone())?;
+                let value = self.evaluate(*value)?;
+                let updated =
+                    self.apply_binary(original.clone(), operator.compound_operator(), value)?;
+                self.set_property(object, name, updated.clone())?;
+                Ok((original, updated))
+            }
+            _ => Err(format!("Can't update {}", expr)),
+        }
+    }
+
+    // instances take part in operators through methods like __add__ and __lt__, and
+    // in concatenation through __str__; None when the operator isn't overloaded
+    fn overloaded(
+        &mut self,
+        left: &Value,
+        operator: &Token,
+        right: &Value,
+    ) -> Option<Result<Value, String>> {
+        let overload = operator.overload()?;
+        let (receiver, argument) = if overload.swapped {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        // equality goes both ways, so "1 == p" asks p when 1 can't answer
+        let (receiver, argument) = match receiver {
+            Value::Instance(_) => (receiver, argument),
+            _ if overload.method == "__eq__" => (argument, receiver),
+            _ => (receiver, argument),
+        };
+        if let Some(result) = self.call_special(receiver, overload.method, vec![argument.clone()]) {
+            let is_predicate = matches!(overload.method, "__eq__" | "__lt__");
+            return Some(result.map(|value| {
+                if is_predicate {
+                    Value::from(self.is_truthy(value) != overload.negated)
+                } else {
+                    value
+                }
+            }));
+        }
+        match (&operator.token_type, left, right) {
+            (TokenType::Plus, Value::String(_), Value::Instance(_))
+            | (TokenType::Plus, Value::Instance(_), Value::String(_)) => {
+                let left = match self.stringify(left.clone()) {
+                    Ok(left) => left,
+                    Err(err) => return Some(Err(err)),
+                };
+                let right = match self.stringify(right.clone()) {
+                    Ok(right) => right,
+                    Err(err) => return Some(Err(err)),
+                };
+                Some(Ok(Value::from(left + &right)))
+            }
+            _ => None,
+        }
+    }
+
+    // calls a method with a special name like __str__, None when the value isn't
+    // an instance or its class doesn't define it
+    fn call_special(
+        &mut self,
+        value: &Value,
+        method: &str,
+        arguments: Vec<Value>,
+    ) -> Option<Result<Value, String>> {
+        let instance = match value {
+            Value::Instance(instance) => instance,
+            _ => return None,
+        };
+        let method = instance.class().find_method(&method.to_string())?.clone();
+        Some(
+            method
+                .bind(instance)
+                .and_then(|method| method.call(self, arguments)),
+        )
+    }
+
+    // what a for-in loop steps through: strings by character, ranges by number,
+    // generators by what they yield, and instances through the iterator their
+    // 'iterator' method gives
+    pub fn iteration(&mut self, keyword: &Token, iterable: Value) -> Result<Iteration, String> {
+        match iterable {
+            Value::String(string) => Ok(Iteration::Values(Box::new(
+                string
+                    .chars()
+                    .map(|character| Value::String(character.to_string()))
+                    .collect::<Vec<Value>>()
+                    .into_iter(),
+            ))),
+            Value::Range(range) => Ok(Iteration::Values(Box::new(range.values().map(Value::Int)))),
+            Value::Generator(generator) => Ok(Iteration::Generator(generator)),
+            _ => match self.call_special(&iterable, "iterator", Vec::new()) {
+                Some(iterator) => match iterator.map_err(|err| with_line(keyword, err))? {
+                    Value::Generator(generator) => Ok(Iteration::Generator(generator)),
+                    iterator => Ok(Iteration::Iterator(Box::new(iterator))),
+                },
+                None => Err(runtime_error(
+                    keyword,
+                    format!(
+                        "Can only iterate over strings, ranges and instances with an 'iterator' method, got {}",
+                        iterable.describe_type()
+                    ),
+                )),
+            },
+        }
+    }
+
+    // how print and concatenation show a value, through __str__ if it has one
+    fn stringify(&mut self, value: Value) -> Result<String, String> {
+        match self.call_special(&value, "__str__", Vec::new()) {
+            Some(result) => Ok(result?.to_string()),
+            None => Ok(value.to_string()),
+        }
+    }
+
+    // the functions a class or trait declares, closing over the current environment
+    fn members(&self, declarations: &[Stmt]) -> Members {
+        let mut members = Members::default();
+        for declaration in declarations {
+            match declaration {
+                // a field's default is run for each new instance, as a method returning it
+                Stmt::VariableDeclaration(name, _, initializer) => {
+                    let initializer = Stmt::FunctionDeclaration(
+                        *name.clone(),
+                        Vec::new(),
+                        vec![Stmt::Return(*name.clone(), initializer.clone())],
+                        Signature::default(),
+                    );
+                    members.fields.push((
+                        name.lexeme.clone(),
+                        Value::Function(Function::new(
+                            initializer,
+                            self.environment.clone(),
+                            false,
+                        )),
+                    ));
+                }
+                Stmt::FunctionDeclaration(name, _, _, signature) => {
+                    let is_initializer =
+                        signature.kind == MethodKind::Method && name.lexeme == "init";
+                    let function = Value::Function(Function::new(
+                        declaration.clone(),
+                        self.environment.clone(),
+                        is_initializer,
+                    ));
+                    let members = match signature.kind {
+                        MethodKind::Method => &mut members.methods,
+                        MethodKind::Static => &mut members.statics,
+                        MethodKind::Getter => &mut members.getters,
+                        MethodKind::Setter => &mut members.setters,
+                    };
+                    members.insert(name.lexeme.clone(), function);
+                }
+                _ => (),
+            }
+        }
+        members
+    }
+
+    // a chain of calls, property reads and indexes; None once a "?." in it has
+    // met nil, which skips the rest of the chain, arguments and all
+    pub fn link(&mut self, expr: Expr) -> Result<Option<Value>, String> {
+        match expr {
+            Expr::OptionalGet(object, name) => match self.link(*object)? {
+                None | Some(Value::Nil) => Ok(None),
+                Some(object) => self.property(object, name).map(Some),
+            },
+            Expr::Get(object, name) => match self.link(*object)? {
+                None => Ok(None),
+                Some(object) => self.property(object, name).map(Some),
+            },
+            Expr::Call(callee, paren, args) => match self.link(*callee)? {
+                None => Ok(None),
+                Some(callee) => self.call_value(callee, paren, args).map(Some),
+            },
+            Expr::Index(object, bracket, index) => match self.link(*object)? {
+                None => Ok(None),
+                Some(object) => self.index(object, bracket, *index).map(Some),
+            },
+            expr => self.evaluate(expr).map(Some),
+        }
+    }
+
+    pub fn call_value(
+        &mut self,
+        callee: Value,
+        paren: Token,
+        args: Vec<Expr>,
+    ) -> Result<Value, String> {
+        let mut arguments = Vec::new();
+        for arg in args {
+            arguments.push(self.evaluate(arg)?);
+        }
+        match callee.arities() {
+            None => {
+                return Err(runtime_error(
+                    &paren,
+                    format!(
+                        "Can only call functions and classes, got {}",
+                        callee.describe_type()
+                    ),
+                ))
+            }
+            Some(arities) if !arities.contains(&arguments.len()) => {
+                return Err(runtime_error(
+                    &paren,
+                    format!(
+                        "Expected {} arguments but got {}",
+                        describe_arities(&arities),
+                        arguments.len()
+                    ),
+                ))
+            }
+            Some(_) => (),
+        }
+        match callee {
+            // errors from lox code already carry their line, natives don't know it
+            // unless they ran some, as a generator's next does
+            Value::NativeFunction(_) => callee
+                .call(self, arguments)
+                .map_err(|err| with_line(&paren, err)),
+            _ => callee.call(self, arguments),
+        }
+    }
+
+    fn index(&mut self, object: Value, bracket: Token, index: Expr) -> Result<Value, String> {
+        let index = self.evaluate(index)?;
+        match self.call_special(&object, "__index__", vec![index]) {
+            Some(result) => result.map_err(|err| with_line(&bracket, err)),
+            None => Err(runtime_error(
+                &bracket,
+                format!(
+                    "Can only index instances with an '__index__' method, got {}",
+                    object.describe_type()
+                ),
+            )),
+        }
+    }
+
+    // classes have properties too, their statics
+    fn property(&mut self, object: Value, name: Token) -> Result<Value, String> {
+        let result = match object {
+            Value::Instance(instance) => instance.get(name.lexeme.clone(), self),
+            Value::Class(klass) => klass.get(&name.lexeme),
+            Value::Generator(ref generator) => match generator.method(&name.lexeme) {
+                Some(method) => Ok(Value::NativeFunction(method)),
+                None => Err(format!("Undefined property '{}'", name.lexeme)),
+            },
+            Value::Channel(ref channel) => match channel.method(&name.lexeme) {
+                Some(method) => Ok(Value::NativeFunction(method)),
+                None => Err(format!("Undefined property '{}'", name.lexeme)),
+            },
+            _ => {
+                return Err(runtime_error(
+                    &name,
+                    format!(
+                        "Only instances have properties, got {}",
+                        object.describe_type()
+                    ),
+                ))
+            }
+        };
+        result.map_err(|err| with_line(&name, err))
+    }
+
+    // goes through the setter if the class has one
+    fn set_property(&mut self, object: Value, name: Token, value: Value) -> Result<(), String> {
+        match object {
+            Value::Instance(instance) => {
+                let setter = instance.class().find_setter(&name.lexeme).cloned();
+                match setter {
+                    Some(setter) => {
+                        setter
+                            .bind(&instance)?
+                            .call(self, vec![value])
+                            .map_err(|err| with_line(&name, err))?;
+                    }
+                    None => instance.set(name.lexeme, value),
+                }
+                Ok(())
+            }
+            Value::Class(klass) => {
+                klass.set(name.lexeme, value);
+                Ok(())
+            }
+            _ => Err(runtime_error(
+                &name,
+                format!("Only instances have fields, got {}", object.describe_type()),
+            )),
+        }
+    }
+
     fn lookup_variable(&self, name: Token, expr: &Expr) -> Result<Value, String> {
-        match self.locals.borrow().get(expr) {
-            Some(distance) => self.environment.borrow().get_at(*distance, name.lexeme),
-            None => self.globals.borrow().get(name.lexeme),
+        let value = match self.locals.borrow().get(expr) {
+            Some(distance) => self
+                .environment
+                .borrow()
+                .get_at(*distance, name.lexeme.clone()),
+            None => self.globals.borrow().get(name.lexeme.clone()),
+        };
+        value.map_err(|err| runtime_error(&name, err))
+    }
+}
+
+// ints, and floats with no fractional part, as the integers bitwise operators work on
+fn as_integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(int) => Some(*int),
+        Value::Number(number) if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => {
+            Some(*number as i64)
+        }
+        _ => None,
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => Some(*number),
+        Value::Int(int) => Some(*int as f64),
+        _ => None,
+    }
+}
+
+// numbers by value, so "got 1.5" says why it isn't an integer
+fn describe_operand(value: &Value) -> String {
+    match value {
+        Value::Number(_) | Value::Int(_) => value.to_string(),
+        _ => value.describe_type(),
+    }
+}
+
+// runtime errors point at the line of the token that caused them
+fn runtime_error(token: &Token, message: String) -> String {
+    format!("[line {}] {}", token.line, message)
+}
+
+// errors raised inside a getter or setter's body already say where they happened
+fn with_line(token: &Token, message: String) -> String {
+    if message.starts_with("[line ") {
+        message
+    } else {
+        runtime_error(token, message)
+    }
+}
+
+fn superclass_name(superclass: &Expr) -> Token {
+    match superclass {
+        Expr::Variable(name) => name.clone(),
+        _ => Token::new(TokenType::Identifier, superclass.to_string(), None, 0),
+    }
+}
+
+// the increment a for loop's body was desugared into a block with, see Parser
+pub fn for_increment(statements: &[Stmt]) -> Option<&Stmt> {
+    match statements {
+        [_, increment @ Stmt::Expression(keyword, _)] if keyword.token_type == TokenType::For => {
+            Some(increment)
         }
+        _ => None,
     }
 }
@@ -0,0 +1,576 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+use super::{
+    expr::Expr, formatter::format_expr, parser::Parser, scanner::Scanner, stmt::Stmt, token::Token,
+    token_type::TokenType,
+};
+
+// lints listed in this file (by code or name, one per line) are never reported
+const CONFIG_FILE: &str = ".loxlint";
+
+// natives defined by the interpreter
+pub const NATIVES: [&str; 8] = [
+    "clock",
+    "assert",
+    "assert_eq",
+    "instanceof",
+    "classOf",
+    "range",
+    "sleep",
+    "channel",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    ShadowedVariable,
+    UnreachableCode,
+    UndeclaredGlobal,
+    ConstantComparison,
+    NonCallable,
+}
+
+const LINTS: [Lint; 7] = [
+    Lint::UnusedVariable,
+    Lint::UnusedParameter,
+    Lint::ShadowedVariable,
+    Lint::UnreachableCode,
+    Lint::UndeclaredGlobal,
+    Lint::ConstantComparison,
+    Lint::NonCallable,
+];
+
+impl Lint {
+    // codes are stable, new lints only ever get new codes
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "L001",
+            Lint::UnusedParameter => "L002",
+            Lint::ShadowedVariable => "L003",
+            Lint::UnreachableCode => "L004",
+            Lint::UndeclaredGlobal => "L005",
+            Lint::ConstantComparison => "L006",
+            Lint::NonCallable => "L007",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::ShadowedVariable => "shadowed-variable",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UndeclaredGlobal => "undeclared-global",
+            Lint::ConstantComparison => "constant-comparison",
+            Lint::NonCallable => "non-callable",
+        }
+    }
+
+    // accepts either the code or the name
+    pub fn find(code_or_name: &str) -> Option<Lint> {
+        LINTS
+            .iter()
+            .find(|lint| lint.code() == code_or_name || lint.name() == code_or_name)
+            .copied()
+    }
+}
+
+#[derive(Debug)]
+pub struct Warning {
+    pub lint: Lint,
+    pub line: i32,
+    pub message: String,
+}
+
+// lints each file, printing its warnings, returns false if anything was reported
+pub fn lint_files(paths: &[String]) -> bool {
+    let allowed = load_config();
+    let mut clean = true;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                println!("Could not read '{}': {}", path, err);
+                clean = false;
+                continue;
+            }
+        };
+        match lint_source(&source, &allowed) {
+            Ok(warnings) => {
+                for warning in &warnings {
+                    println!(
+                        "{}:{}: warning[{}] {} ({})",
+                        path,
+                        warning.line,
+                        warning.lint.code(),
+                        warning.message,
+                        warning.lint.name()
+                    );
+                }
+                clean = clean && warnings.is_empty();
+            }
+            Err(err) => {
+                println!("{}: {}", path, err);
+                clean = false;
+            }
+        }
+    }
+    clean
+}
+
+pub fn load_config() -> HashSet<Lint> {
+    let mut allowed = HashSet::new();
+    if let Ok(config) = fs::read_to_string(CONFIG_FILE) {
+        for entry in config.lines().map(str::trim) {
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            match Lint::find(entry) {
+                Some(lint) => {
+                    allowed.insert(lint);
+                }
+                None => println!("{}: unknown lint '{}'", CONFIG_FILE, entry),
+            }
+        }
+    }
+    allowed
+}
+
+pub fn lint_source(source: &str, allowed: &HashSet<Lint>) -> Result<Vec<Warning>, String> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens()?;
+    let stmts = Parser::new(tokens).parse()?;
+    let suppressions = Suppressions::new(&scanner.comments(), allowed);
+
+    let mut linter = Linter {
+        scopes: Vec::new(),
+        globals: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    linter
+        .globals
+        .extend(NATIVES.iter().map(|native| native.to_string()));
+    for stmt in &stmts {
+        match stmt {
+            Stmt::VariableDeclaration(name, _, _)
+            | Stmt::ClassDeclaration(name, _, _, _)
+            | Stmt::TraitDeclaration(name, _) => {
+                linter.globals.insert(name.lexeme.clone());
+            }
+            Stmt::FunctionDeclaration(name, _, _, _) => {
+                linter.globals.insert(name.lexeme.clone());
+            }
+            _ => (),
+        }
+    }
+    linter.statements(&stmts);
+
+    let mut warnings = linter
+        .warnings
+        .into_iter()
+        .filter(|warning| !suppressions.allows(warning))
+        .collect::<Vec<Warning>>();
+    warnings.sort_by(|a, b| (a.line, a.lint.code()).cmp(&(b.line, b.lint.code())));
+    Ok(warnings)
+}
+
+// "// lint-allow: L001, unused-parameter" silences lints on its own line and the next one
+// "// lint-allow-file: L003" silences lints in the whole file
+struct Suppressions {
+    file: HashSet<Lint>,
+    lines: HashSet<(i32, Lint)>,
+}
+
+impl Suppressions {
+    fn new(comments: &[Token], allowed: &HashSet<Lint>) -> Self {
+        let mut suppressions = Self {
+            file: allowed.clone(),
+            lines: HashSet::new(),
+        };
+        for comment in comments {
+            let text = comment.lexeme.trim_start_matches('/').trim();
+            if let Some(lints) = text.strip_prefix("lint-allow-file:") {
+                suppressions.file.extend(parse_lints(lints));
+            } else if let Some(lints) = text.strip_prefix("lint-allow:") {
+                for lint in parse_lints(lints) {
+                    suppressions.lines.insert((comment.line, lint));
+                    suppressions.lines.insert((comment.line + 1, lint));
+                }
+            }
+        }
+        suppressions
+    }
+
+    fn allows(&self, warning: &Warning) -> bool {
+        self.file.contains(&warning.lint) || self.lines.contains(&(warning.line, warning.lint))
+    }
+}
+
+fn parse_lints(list: &str) -> Vec<Lint> {
+    list.split(|ch: char| ch == ',' || ch.is_whitespace())
+        .filter_map(Lint::find)
+        .collect()
+}
+
+struct Local {
+    name: Token,
+    used: bool,
+    is_parameter: bool,
+}
+
+struct Linter {
+    scopes: Vec<HashMap<String, Local>>,
+    // every name declared at the top level, wherever it is declared
+    globals: HashSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl Linter {
+    fn warn(&mut self, lint: Lint, line: i32, message: String) {
+        self.warnings.push(Warning {
+            lint,
+            line,
+            message,
+        });
+    }
+
+    fn statements(&mut self, stmts: &[Stmt]) {
+        // the keyword of the statement that ended the block, if any
+        let mut terminator = None;
+        for stmt in stmts {
+            if let Some(keyword) = terminator {
+                // a for loop's increment runs after 'continue' too, see Parser
+                if !matches!(stmt, Stmt::Expression(token, _) if token.token_type == TokenType::For)
+                {
+                    self.warn(
+                        Lint::UnreachableCode,
+                        stmt.line(),
+                        format!("unreachable statement after '{}'", keyword),
+                    );
+                }
+                // only the first unreachable statement is reported
+                terminator = None;
+            } else {
+                terminator = match stmt {
+                    Stmt::Return(_, _) => Some("return"),
+                    Stmt::Break(_) => Some("break"),
+                    Stmt::Continue(_) => Some("continue"),
+                    _ => None,
+                };
+            }
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(_, stmts) => {
+                self.begin_scope();
+                self.statements(stmts);
+                self.end_scope();
+            }
+            Stmt::Expression(_, expr)
+            | Stmt::Print(_, expr)
+            | Stmt::Return(_, expr)
+            | Stmt::Yield(_, expr) => self.expression(expr),
+            Stmt::Break(_) | Stmt::Continue(_) => (),
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_stmt) = &**else_branch {
+                    self.statement(else_stmt);
+                }
+            }
+            Stmt::While(_, condition, body) => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            Stmt::ForIn(_, name, iterable, body) => {
+                self.expression(iterable);
+                self.begin_scope();
+                self.declare(name, false);
+                self.statement(body);
+                self.end_scope();
+            }
+            Stmt::VariableDeclaration(name, _, initializer) => {
+                self.expression(initializer);
+                self.declare(name, false);
+            }
+            Stmt::FunctionDeclaration(name, _, _, _) => {
+                self.declare(name, false);
+                self.function(stmt);
+            }
+            Stmt::ClassDeclaration(name, superclass, traits, methods) => {
+                self.declare(name, false);
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                for mixed in traits {
+                    self.expression(mixed);
+                }
+                for method in methods {
+                    match method {
+                        Stmt::VariableDeclaration(_, _, initializer) => {
+                            self.expression(initializer)
+                        }
+                        _ => self.function(method),
+                    }
+                }
+            }
+            Stmt::TraitDeclaration(name, methods) => {
+                self.declare(name, false);
+                for method in methods {
+                    match method {
+                        Stmt::VariableDeclaration(_, _, initializer) => {
+                            self.expression(initializer)
+                        }
+                        _ => self.function(method),
+                    }
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Stmt) {
+        if let Stmt::FunctionDeclaration(_, params, body, _) = function {
+            self.begin_scope();
+            for param in params {
+                self.declare(param, true);
+            }
+            self.statements(body);
+            self.end_scope();
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign(name, value) => {
+                self.expression(value);
+                let is_local = self
+                    .scopes
+                    .iter()
+                    .any(|scope| scope.contains_key(&name.lexeme));
+                if !is_local && !self.globals.contains(&name.lexeme) {
+                    self.warn(
+                        Lint::UndeclaredGlobal,
+                        name.line,
+                        format!("assignment to undeclared variable '{}'", name.lexeme),
+                    );
+                }
+            }
+            Expr::Binary(left, operator, right) => {
+                self.expression(left);
+                self.expression(right);
+                if let Some(result) = constant_comparison(left, operator, right) {
+                    self.warn(
+                        Lint::ConstantComparison,
+                        operator.line,
+                        format!(
+                            "'{} {} {}' is always {}",
+                            format_expr(left),
+                            operator.lexeme,
+                            format_expr(right),
+                            result
+                        ),
+                    );
+                }
+            }
+            Expr::Logical(left, _, right) | Expr::Index(left, _, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Grouping(expression)
+            | Expr::Urnary(_, expression)
+            | Expr::Await(_, expression)
+            | Expr::Spawn(_, expression) => self.expression(expression),
+            Expr::Variable(name) => {
+                for scope in self.scopes.iter_mut().rev() {
+                    if let Some(local) = scope.get_mut(&name.lexeme) {
+                        local.used = true;
+                        return;
+                    }
+                }
+            }
+            Expr::Call(callee, paren, arguments) => {
+                if let Some(kind) = literal_kind(callee) {
+                    self.warn(
+                        Lint::NonCallable,
+                        paren.line,
+                        format!("calling a {} literal always fails", kind),
+                    );
+                }
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expr::Get(object, _) | Expr::OptionalGet(object, _) => self.expression(object),
+            Expr::Conditional(condition, _, then_branch, else_branch) => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expr::Set(object, _, value) | Expr::CompoundSet(object, _, _, value) => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::CompoundAssign(name, _, value) => {
+                // reads the variable as well as writing it
+                self.expression(&Expr::Variable(name.clone()));
+                self.expression(&Expr::Assign(name.clone(), value.clone()));
+            }
+            Expr::Postfix(update, _) => self.expression(update),
+            Expr::StringLiteral(_)
+            | Expr::NumberLiteral(..)
+            | Expr::IntLiteral(..)
+            | Expr::NilLiteral
+            | Expr::TrueLiteral
+            | Expr::FalseLiteral
+            | Expr::This(_)
+            | Expr::Super(_, _) => (),
+        }
+    }
+
+    fn declare(&mut self, name: &Token, is_parameter: bool) {
+        if self.scopes.is_empty() {
+            return;
+        }
+
+        let shadowed = self
+            .scopes
+            .iter()
+            .rev()
+            .skip(1)
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|local| format!("line {}", local.name.line));
+        let shadowed = shadowed.or_else(|| {
+            if self.globals.contains(&name.lexeme) {
+                Some("the global scope".to_string())
+            } else {
+                None
+            }
+        });
+        if let Some(location) = shadowed {
+            self.warn(
+                Lint::ShadowedVariable,
+                name.line,
+                format!("'{}' shadows a variable from {}", name.lexeme, location),
+            );
+        }
+
+        self.scopes.last_mut().unwrap().insert(
+            name.lexeme.clone(),
+            Local {
+                name: name.clone(),
+                used: false,
+                is_parameter,
+            },
+        );
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        // in the order they were declared, as a hashmap's order changes from run to run
+        let mut locals = scope.values().collect::<Vec<&Local>>();
+        locals.sort_by_key(|local| (local.name.line, local.name.column));
+        for local in locals {
+            // a leading underscore marks a variable as deliberately unused
+            if local.used || local.name.lexeme.starts_with('_') {
+                continue;
+            }
+            if local.is_parameter {
+                self.warn(
+                    Lint::UnusedParameter,
+                    local.name.line,
+                    format!("unused parameter '{}'", local.name.lexeme),
+                );
+            } else {
+                self.warn(
+                    Lint::UnusedVariable,
+                    local.name.line,
+                    format!("unused variable '{}'", local.name.lexeme),
+                );
+            }
+        }
+    }
+}
+
+fn literal_kind(expr: &Expr) -> Option<&'static str> {
+    match expr {
+        Expr::Grouping(expression) => literal_kind(expression),
+        Expr::StringLiteral(_) => Some("string"),
+        Expr::NumberLiteral(..) | Expr::IntLiteral(..) => Some("number"),
+        Expr::NilLiteral => Some("nil"),
+        Expr::TrueLiteral | Expr::FalseLiteral => Some("boolean"),
+        _ => None,
+    }
+}
+
+// the outcome of a comparison whose result doesn't depend on runtime values: one
+// between literals, or between a variable and itself; properties and 'this' don't
+// qualify, as getters and __eq__ overloads run code that can answer differently
+fn constant_comparison(left: &Expr, operator: &Token, right: &Expr) -> Option<bool> {
+    let (left, right) = (unwrap_grouping(left), unwrap_grouping(right));
+    if let (Expr::Variable(left), Expr::Variable(right)) = (left, right) {
+        if left.lexeme != right.lexeme {
+            return None;
+        }
+        return match operator.token_type {
+            TokenType::EqualEqual | TokenType::GreaterEqual | TokenType::LessEqual => Some(true),
+            TokenType::BangEqual | TokenType::Greater | TokenType::Less => Some(false),
+            _ => None,
+        };
+    }
+    if literal_kind(left).is_none() || literal_kind(right).is_none() {
+        return None;
+    }
+    if let Some(ordering) = compare_numbers(left, right) {
+        return match operator.token_type {
+            TokenType::EqualEqual => Some(ordering == Ordering::Equal),
+            TokenType::BangEqual => Some(ordering != Ordering::Equal),
+            TokenType::Greater => Some(ordering == Ordering::Greater),
+            TokenType::GreaterEqual => Some(ordering != Ordering::Less),
+            TokenType::Less => Some(ordering == Ordering::Less),
+            TokenType::LessEqual => Some(ordering != Ordering::Greater),
+            _ => None,
+        };
+    }
+    match operator.token_type {
+        TokenType::EqualEqual => Some(left == right),
+        TokenType::BangEqual => Some(left != right),
+        _ => None,
+    }
+}
+
+// ints compare as ints, since casting 9007199254740993 to a float would make it
+// equal to its neighbour; an int against a float compares by value, so 1 == 1.0
+fn compare_numbers(left: &Expr, right: &Expr) -> Option<Ordering> {
+    match (left, right) {
+        (Expr::IntLiteral(left, _), Expr::IntLiteral(right, _)) => Some(left.cmp(right)),
+        _ => number_literal(left)?.partial_cmp(&number_literal(right)?),
+    }
+}
+
+fn number_literal(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::NumberLiteral(number, _) => Some(*number),
+        Expr::IntLiteral(int, _) => Some(*int as f64),
+        _ => None,
+    }
+}
+
+fn unwrap_grouping(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Grouping(expression) => unwrap_grouping(expression),
+        _ => expr,
+    }
+}
@@ -0,0 +1,659 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value as Json};
+
+use super::{
+    checker::Checker,
+    interpreter::Interpreter,
+    linter::{lint_source, load_config, NATIVES},
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    stmt::Stmt,
+    token::Token,
+    token_type::TokenType,
+};
+
+const KEYWORDS: [&str; 25] = [
+    "and", "await", "break", "class", "continue", "else", "false", "for", "fun", "if", "in", "is",
+    "nil", "or", "print", "return", "spawn", "super", "this", "trait", "true", "var", "while",
+    "with", "yield",
+];
+
+// LSP severities
+const ERROR: i32 = 1;
+const WARNING: i32 = 2;
+
+// LSP symbol and completion kinds
+const SYMBOL_CLASS: i32 = 5;
+const SYMBOL_METHOD: i32 = 6;
+const SYMBOL_INTERFACE: i32 = 11;
+const SYMBOL_FUNCTION: i32 = 12;
+const SYMBOL_VARIABLE: i32 = 13;
+const COMPLETION_FUNCTION: i32 = 3;
+const COMPLETION_VARIABLE: i32 = 6;
+const COMPLETION_CLASS: i32 = 7;
+const COMPLETION_INTERFACE: i32 = 8;
+const COMPLETION_KEYWORD: i32 = 14;
+
+// JSON-RPC errors
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_REQUEST: i32 = -32600;
+
+#[derive(Clone, PartialEq)]
+enum Kind {
+    // superclass name
+    Class(Option<String>),
+    Trait,
+    // parameter names
+    Function(Vec<String>),
+    Method(Vec<String>),
+    Variable,
+    Parameter,
+}
+
+#[derive(Clone)]
+struct Declaration {
+    name: Token,
+    kind: Kind,
+    // declared at the top level, so reachable by name from anywhere
+    global: bool,
+    // methods of a class
+    children: Vec<Declaration>,
+}
+
+// everything the server knows about one open file
+#[derive(Default)]
+struct Document {
+    uri: String,
+    text: String,
+    // from the last version that parsed and resolved
+    declarations: Vec<Declaration>,
+    references: Vec<(Token, Option<Token>)>,
+}
+
+// a language server speaking JSON-RPC with Content-Length framing,
+// over any reader and writer so a session can be scripted
+pub struct Server<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    // runs until the client sends exit or closes the stream
+    // returns the exit code the process should use
+    pub fn run(&mut self) -> io::Result<i32> {
+        while let Some(message) = self.read_message()? {
+            let message: Json = match serde_json::from_str(&message) {
+                Ok(message) => message,
+                Err(err) => {
+                    self.send_error(Json::Null, INVALID_REQUEST, &err.to_string())?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or("").to_string();
+            if method == "exit" {
+                return Ok(if self.shutdown { 0 } else { 1 });
+            }
+            let params = &message["params"];
+            let result = match method.as_str() {
+                "initialize" => Some(initialize_result()),
+                "shutdown" => {
+                    self.shutdown = true;
+                    Some(Json::Null)
+                }
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    self.update(document["uri"].as_str(), document["text"].as_str())?;
+                    None
+                }
+                "textDocument/didChange" => {
+                    // full sync, so the last change holds the whole text
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+                    self.update(params["textDocument"]["uri"].as_str(), text)?;
+                    None
+                }
+                "textDocument/didClose" => {
+                    if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                        self.documents.remove(uri);
+                        self.publish_diagnostics(uri, Vec::new())?;
+                    }
+                    None
+                }
+                "textDocument/definition" => Some(self.with_position(params, definition)),
+                "textDocument/references" => {
+                    let include_declaration = params["context"]["includeDeclaration"]
+                        .as_bool()
+                        .unwrap_or(true);
+                    Some(self.with_position(params, |document, line, column| {
+                        references(document, line, column, include_declaration)
+                    }))
+                }
+                "textDocument/hover" => Some(self.with_position(params, hover)),
+                "textDocument/documentSymbol" => Some(match self.document(params) {
+                    Some(document) => document_symbols(document),
+                    None => Json::Null,
+                }),
+                "textDocument/completion" => Some(match self.document(params) {
+                    Some(document) => completions(document),
+                    None => Json::Null,
+                }),
+                _ => {
+                    // notifications we don't handle are dropped, requests get an error
+                    if !message["id"].is_null() && !method.starts_with("$/") {
+                        self.send_error(
+                            message["id"].clone(),
+                            METHOD_NOT_FOUND,
+                            &format!("Unhandled method {}", method),
+                        )?;
+                    }
+                    None
+                }
+            };
+            if let Some(result) = result {
+                self.send(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }))?;
+            }
+        }
+        Ok(if self.shutdown { 0 } else { 1 })
+    }
+
+    fn document(&self, params: &Json) -> Option<&Document> {
+        params["textDocument"]["uri"]
+            .as_str()
+            .and_then(|uri| self.documents.get(uri))
+    }
+
+    // looks up the document and turns the LSP position into a line and byte column
+    fn with_position<F>(&self, params: &Json, handler: F) -> Json
+    where
+        F: Fn(&Document, i32, usize) -> Json,
+    {
+        let document = match self.document(params) {
+            Some(document) => document,
+            None => return Json::Null,
+        };
+        let position = &params["position"];
+        let line = position["line"].as_i64().unwrap_or(0) as i32 + 1;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let column = byte_column(&document.text, line, character);
+        handler(document, line, column)
+    }
+
+    fn update(&mut self, uri: Option<&str>, text: Option<&str>) -> io::Result<()> {
+        let (uri, text) = match (uri, text) {
+            (Some(uri), Some(text)) => (uri, text),
+            _ => return Ok(()),
+        };
+        let document = self.documents.entry(uri.to_string()).or_default();
+        document.uri = uri.to_string();
+        document.text = text.to_string();
+        let diagnostics = analyze(document);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn send_error(&mut self, id: Json, code: i32, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }))
+    }
+
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        write_message(&mut self.writer, &message)
+    }
+
+    fn read_message(&mut self) -> io::Result<Option<String>> {
+        read_message(&mut self.reader)
+    }
+}
+
+// the debug adapter protocol frames its messages the same way
+pub fn write_message<W: Write>(writer: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+// None once the stream is closed
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+fn initialize_result() -> Json {
+    json!({
+        "capabilities": {
+            // 1 = the client sends the whole text on every change
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "referencesProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "completionProvider": {},
+        },
+        "serverInfo": { "name": "lox", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+// reruns the front end on the document, keeping the symbols from the last good version
+// when the new text doesn't parse, and returns its diagnostics
+fn analyze(document: &mut Document) -> Vec<Json> {
+    let text = document.text.clone();
+    let mut diagnostics = Vec::new();
+    let stmts = Scanner::new(&text)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse());
+    match stmts {
+        Ok(stmts) => {
+            let interpreter = Interpreter::new();
+            let mut resolver = Resolver::new(&interpreter);
+            let result = resolver
+                .resolve(&stmts)
+                .and_then(|_| Checker::new().check(&stmts));
+            if let Err(err) = result {
+                diagnostics.extend(error_diagnostics(&text, &err));
+            }
+            document.declarations = declarations(&stmts, true);
+            document.references = resolver.references();
+        }
+        Err(err) => diagnostics.extend(error_diagnostics(&text, &err)),
+    }
+    if let Ok(warnings) = lint_source(&text, &load_config()) {
+        for warning in warnings {
+            diagnostics.push(json!({
+                "range": line_range(&text, warning.line),
+                "severity": WARNING,
+                "code": warning.lint.code(),
+                "source": "lox lint",
+                "message": format!("{} ({})", warning.message, warning.lint.name()),
+            }));
+        }
+    }
+    diagnostics
+}
+
+// front end errors look like "Line 3: message", one per line
+fn error_diagnostics(text: &str, errors: &str) -> Vec<Json> {
+    errors
+        .lines()
+        .map(|error| {
+            let (line, message) = match error
+                .strip_prefix("Line ")
+                .and_then(|rest| rest.split_once(": "))
+                .and_then(|(line, message)| Some((line.parse::<i32>().ok()?, message)))
+            {
+                Some((line, message)) => (line, message),
+                None => (1, error),
+            };
+            json!({
+                "range": line_range(text, line),
+                "severity": ERROR,
+                "source": "lox",
+                "message": message,
+            })
+        })
+        .collect()
+}
+
+fn declarations(stmts: &[Stmt], global: bool) -> Vec<Declaration> {
+    let mut found = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Stmt::VariableDeclaration(name, _, _) => found.push(Declaration {
+                name: *name.clone(),
+                kind: Kind::Variable,
+                global,
+                children: Vec::new(),
+            }),
+            Stmt::FunctionDeclaration(name, params, body, _) => {
+                found.push(Declaration {
+                    name: name.clone(),
+                    kind: Kind::Function(param_names(params)),
+                    global,
+                    children: Vec::new(),
+                });
+                found.extend(params.iter().map(|param| Declaration {
+                    name: param.clone(),
+                    kind: Kind::Parameter,
+                    global: false,
+                    children: Vec::new(),
+                }));
+                found.extend(declarations(body, false));
+            }
+            Stmt::ClassDeclaration(name, superclass, _, methods) => {
+                let superclass = match superclass {
+                    Some(super::expr::Expr::Variable(superclass)) => {
+                        Some(superclass.lexeme.clone())
+                    }
+                    _ => None,
+                };
+                let (children, nested) = method_declarations(methods);
+                found.push(Declaration {
+                    name: *name.clone(),
+                    kind: Kind::Class(superclass),
+                    global,
+                    children,
+                });
+                found.extend(nested);
+            }
+            Stmt::TraitDeclaration(name, methods) => {
+                let (children, nested) = method_declarations(methods);
+                found.push(Declaration {
+                    name: *name.clone(),
+                    kind: Kind::Trait,
+                    global,
+                    children,
+                });
+                found.extend(nested);
+            }
+            Stmt::Block(_, stmts) => found.extend(declarations(stmts, false)),
+            Stmt::If(_, _, then_branch, else_branch) => {
+                found.extend(declarations(std::slice::from_ref(&**then_branch), false));
+                if let Some(else_branch) = &**else_branch {
+                    found.extend(declarations(std::slice::from_ref(else_branch), false));
+                }
+            }
+            Stmt::While(_, _, body) => {
+                found.extend(declarations(std::slice::from_ref(&**body), false))
+            }
+            Stmt::ForIn(_, name, _, body) => {
+                found.push(Declaration {
+                    name: *name.clone(),
+                    kind: Kind::Variable,
+                    global: false,
+                    children: Vec::new(),
+                });
+                found.extend(declarations(std::slice::from_ref(&**body), false));
+            }
+            Stmt::Expression(_, _)
+            | Stmt::Print(_, _)
+            | Stmt::Return(_, _)
+            | Stmt::Yield(_, _)
+            | Stmt::Break(_)
+            | Stmt::Continue(_) => {}
+        }
+    }
+    found
+}
+
+// the methods of a class or trait, and what's declared inside them
+fn method_declarations(methods: &[Stmt]) -> (Vec<Declaration>, Vec<Declaration>) {
+    let mut children = Vec::new();
+    let mut nested = Vec::new();
+    for method in methods {
+        if let Stmt::FunctionDeclaration(method_name, params, body, _) = method {
+            children.push(Declaration {
+                name: method_name.clone(),
+                kind: Kind::Method(param_names(params)),
+                global: false,
+                children: Vec::new(),
+            });
+            nested.extend(params.iter().map(|param| Declaration {
+                name: param.clone(),
+                kind: Kind::Parameter,
+                global: false,
+                children: Vec::new(),
+            }));
+            nested.extend(declarations(body, false));
+        }
+    }
+    (children, nested)
+}
+
+fn param_names(params: &[Token]) -> Vec<String> {
+    params.iter().map(|param| param.lexeme.clone()).collect()
+}
+
+// every declaration in the document, methods included
+fn all_declarations(document: &Document) -> Vec<&Declaration> {
+    let mut all = Vec::new();
+    for declaration in &document.declarations {
+        all.push(declaration);
+        all.extend(declaration.children.iter());
+    }
+    all
+}
+
+fn contains(token: &Token, line: i32, column: usize) -> bool {
+    token.line == line && column >= token.column && column <= token.column + token.lexeme.len()
+}
+
+// the declaration the identifier under the cursor refers to
+fn declaration_at(document: &Document, line: i32, column: usize) -> Option<&Declaration> {
+    let declarations = all_declarations(document);
+    if let Some(declaration) = declarations
+        .iter()
+        .find(|declaration| contains(&declaration.name, line, column))
+    {
+        return Some(declaration);
+    }
+    let (usage, target) = document.references.iter().find(|(usage, _)| {
+        usage.token_type == TokenType::Identifier && contains(usage, line, column)
+    })?;
+    match target {
+        Some(target) => declarations
+            .into_iter()
+            .find(|declaration| declaration.name == *target),
+        // unresolved uses are globals, the first top-level declaration wins
+        None => declarations
+            .into_iter()
+            .find(|declaration| declaration.global && declaration.name.lexeme == usage.lexeme),
+    }
+}
+
+fn definition(document: &Document, line: i32, column: usize) -> Json {
+    match declaration_at(document, line, column) {
+        Some(declaration) => json!([location(document, &declaration.name)]),
+        None => Json::Null,
+    }
+}
+
+fn references(document: &Document, line: i32, column: usize, include_declaration: bool) -> Json {
+    let declaration = match declaration_at(document, line, column) {
+        Some(declaration) => declaration,
+        None => return Json::Null,
+    };
+    let mut locations = Vec::new();
+    if include_declaration {
+        locations.push(location(document, &declaration.name));
+    }
+    for (usage, target) in &document.references {
+        let refers = match target {
+            Some(target) => *target == declaration.name,
+            None => declaration.global && usage.lexeme == declaration.name.lexeme,
+        };
+        if refers && usage.token_type == TokenType::Identifier {
+            locations.push(location(document, usage));
+        }
+    }
+    json!(locations)
+}
+
+fn hover(document: &Document, line: i32, column: usize) -> Json {
+    let declaration = match declaration_at(document, line, column) {
+        Some(declaration) => declaration,
+        None => return Json::Null,
+    };
+    let name = &declaration.name.lexeme;
+    let detail = match &declaration.kind {
+        Kind::Class(Some(superclass)) => format!("class {} < {}", name, superclass),
+        Kind::Class(None) => format!("class {}", name),
+        Kind::Trait => format!("trait {}", name),
+        Kind::Function(params) | Kind::Method(params) => {
+            let prefix = if let Kind::Function(_) = declaration.kind {
+                "fun "
+            } else {
+                ""
+            };
+            format!(
+                "{}{}({})\narity {}",
+                prefix,
+                name,
+                params.join(", "),
+                params.len()
+            )
+        }
+        Kind::Variable => format!("var {}", name),
+        Kind::Parameter => format!("parameter {}", name),
+    };
+    json!({
+        "contents": { "kind": "markdown", "value": format!("```lox\n{}\n```", detail) },
+        "range": range(document, &declaration.name),
+    })
+}
+
+fn document_symbols(document: &Document) -> Json {
+    let symbol = |declaration: &Declaration, kind: i32, children: Vec<Json>| {
+        let range = range(document, &declaration.name);
+        json!({
+            "name": declaration.name.lexeme,
+            "kind": kind,
+            "range": range,
+            "selectionRange": range,
+            "children": children,
+        })
+    };
+    let symbols = document
+        .declarations
+        .iter()
+        .filter_map(|declaration| match &declaration.kind {
+            Kind::Class(_) | Kind::Trait => {
+                let methods = declaration
+                    .children
+                    .iter()
+                    .map(|method| symbol(method, SYMBOL_METHOD, Vec::new()))
+                    .collect();
+                let kind = match declaration.kind {
+                    Kind::Trait => SYMBOL_INTERFACE,
+                    _ => SYMBOL_CLASS,
+                };
+                Some(symbol(declaration, kind, methods))
+            }
+            Kind::Function(_) => Some(symbol(declaration, SYMBOL_FUNCTION, Vec::new())),
+            Kind::Variable if declaration.global => {
+                Some(symbol(declaration, SYMBOL_VARIABLE, Vec::new()))
+            }
+            _ => None,
+        })
+        .collect::<Vec<Json>>();
+    json!(symbols)
+}
+
+fn completions(document: &Document) -> Json {
+    let mut items = KEYWORDS
+        .iter()
+        .map(|keyword| json!({ "label": keyword, "kind": COMPLETION_KEYWORD }))
+        .collect::<Vec<Json>>();
+    let mut seen = Vec::new();
+    for native in NATIVES {
+        seen.push(native.to_string());
+        items.push(json!({ "label": native, "kind": COMPLETION_FUNCTION }));
+    }
+    for declaration in &document.declarations {
+        let name = &declaration.name.lexeme;
+        if seen.contains(name) {
+            continue;
+        }
+        seen.push(name.clone());
+        let kind = match declaration.kind {
+            Kind::Class(_) => COMPLETION_CLASS,
+            Kind::Trait => COMPLETION_INTERFACE,
+            Kind::Function(_) | Kind::Method(_) => COMPLETION_FUNCTION,
+            Kind::Variable | Kind::Parameter => COMPLETION_VARIABLE,
+        };
+        items.push(json!({ "label": name, "kind": kind }));
+    }
+    json!(items)
+}
+
+fn location(document: &Document, token: &Token) -> Json {
+    json!({ "uri": document.uri, "range": range(document, token) })
+}
+
+fn range(document: &Document, token: &Token) -> Json {
+    let start = utf16_column(&document.text, token.line, token.column);
+    let end = utf16_column(
+        &document.text,
+        token.line,
+        token.column + token.lexeme.len(),
+    );
+    json!({
+        "start": { "line": token.line - 1, "character": start },
+        "end": { "line": token.line - 1, "character": end },
+    })
+}
+
+fn line_range(text: &str, line: i32) -> Json {
+    let length = source_line(text, line).encode_utf16().count();
+    json!({
+        "start": { "line": line - 1, "character": 0 },
+        "end": { "line": line - 1, "character": length },
+    })
+}
+
+fn source_line(text: &str, line: i32) -> &str {
+    text.lines().nth((line - 1).max(0) as usize).unwrap_or("")
+}
+
+// LSP columns count UTF-16 code units, the scanner counts bytes
+fn utf16_column(text: &str, line: i32, column: usize) -> usize {
+    let source_line = source_line(text, line);
+    source_line
+        .get(..column.min(source_line.len()))
+        .map_or(0, |prefix| prefix.encode_utf16().count())
+}
+
+fn byte_column(text: &str, line: i32, character: usize) -> usize {
+    let mut units = 0;
+    for (index, ch) in source_line(text, line).char_indices() {
+        if units >= character {
+            return index;
+        }
+        units += ch.len_utf16();
+    }
+    source_line(text, line).len()
+}
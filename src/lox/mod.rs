@@ -1,107 +1,159 @@
 mod callable;
+mod checker;
 mod class;
+mod coverage;
+pub mod dap;
+mod debugger;
 mod environment;
 pub mod expr;
+pub mod formatter;
+mod generator;
 mod instance;
 mod interpreter;
+pub mod linter;
+pub mod lsp;
+mod optimizer;
 mod parser;
+mod profiler;
+mod repl;
 mod resolver;
 mod scanner;
 pub mod stmt;
+mod task;
+pub mod test_runner;
 pub mod token;
 pub mod token_type;
 pub mod value;
 
-use std::{
-    fs,
-    io::{self, Write},
-    process::exit,
-};
+use std::{fs, process::exit};
 
 use crate::lox::interpreter::Interpreter;
 
-use self::{parser::Parser, resolver::Resolver, scanner::Scanner};
+use self::{
+    checker::Checker, optimizer::Optimizer, parser::Parser, resolver::Resolver, scanner::Scanner,
+    stmt::Stmt,
+};
 
-pub struct Lox {}
+pub struct Lox {
+    // kept across runs so the REPL remembers earlier definitions
+    interpreter: Interpreter,
+    // whether programs are folded and pruned before they run, see Optimizer
+    optimize: bool,
+    // whether types are checked before programs run, see Checker
+    check: bool,
+}
 
 impl Lox {
-    pub fn run_file(&self, file_name: &str) {
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+            optimize: true,
+            check: true,
+        }
+    }
+
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn set_check(&mut self, check: bool) {
+        self.check = check;
+    }
+
+    pub fn run_file(&mut self, file_name: &str) {
         // lifetime of source is this block
         let source: String =
             fs::read_to_string(file_name).expect("Something went wrong reading the file");
         self.run(&source, false);
     }
 
-    pub fn run_prompt(&self) {
-        loop {
-            // lifetime of line is this loop
-            let mut line = String::new();
-            print!("> ");
-            io::stdout().flush().expect("something went wrong");
-            io::stdin()
-                .read_line(&mut line)
-                .expect("something went wrong");
-            // run borrows line
-            self.run(&line, true);
-            print!("\n");
-        }
-    }
-
-    fn run(&self, source: &str, reset_errors: bool) {
+    // errors go to stderr, and outside the REPL end the process with 65 for
+    // static errors or 70 for runtime errors, as in the book
+    fn run(&mut self, source: &str, reset_errors: bool) {
         // lifetime of source depends on caller
-        let mut scanner = Scanner::new(source);
-        let result_tokens = scanner.scan_tokens();
-
-        match result_tokens {
-            Ok(ref tokens) => {
-                for token in tokens {
-                    println!("{}", token);
-                }
-            }
-            Err(ref err) => {
-                println!("{}", err);
+        let stmts = match Self::parse_source(source) {
+            Ok(stmts) => stmts,
+            Err(err) => {
+                eprintln!("{}", err);
                 if !reset_errors {
                     exit(65);
                 }
                 return;
             }
-        }
+        };
 
-        let parser = Parser::new(result_tokens.expect("something went very wrong"));
-        let stmts = parser.parse();
-        match stmts {
-            Ok(ref stmts) => {
-                for stmt in stmts {
-                    println!("{}", stmt);
-                }
-            }
+        let stmts = match self.analyze(&stmts).and_then(|_| self.optimize(stmts)) {
+            Ok(stmts) => stmts,
             Err(err) => {
-                println!("{}", err);
+                eprintln!("{}", err);
                 if !reset_errors {
                     exit(65);
                 }
                 return;
             }
-        }
+        };
 
-        let mut interpreter = Interpreter::new();
-        let mut resolver = Resolver::new(&interpreter);
-        let resolution = resolver.resolve(&stmts.clone().unwrap());
+        let value = self.interpreter.interpret(stmts);
 
-        if let Err(err) = resolution {
-            println!("{}", err);
+        if let Err(err) = value {
+            eprintln!("{}", err);
             if !reset_errors {
-                exit(65);
+                exit(70);
             }
         }
+    }
 
-        let value = interpreter.interpret(stmts.expect("failed at interpreting"));
-
-        if let Err(err) = value {
-            println!("{}", err);
-            if !reset_errors {
+    // reads, parses and resolves a file for the tools that run it their own way,
+    // exiting like run does if that fails
+    fn load_file(&self, file_name: &str) -> (String, Vec<Stmt>) {
+        let source = match fs::read_to_string(file_name) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Could not read '{}': {}", file_name, err);
+                exit(66);
+            }
+        };
+        let stmts = match Self::parse_source(&source) {
+            Ok(stmts) => stmts,
+            Err(err) => {
+                eprintln!("{}", err);
                 exit(65);
             }
+        };
+        if let Err(err) = self.analyze(&stmts) {
+            eprintln!("{}", err);
+            exit(65);
         }
+        (source, stmts)
+    }
+
+    fn parse_source(source: &str) -> Result<Vec<Stmt>, String> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        Parser::new(tokens).parse()
+    }
+
+    // the static checks, scoping and then types
+    fn analyze(&self, stmts: &Vec<Stmt>) -> Result<(), String> {
+        self.resolve(stmts)?;
+        if !self.check {
+            return Ok(());
+        }
+        Checker::new().check(stmts)
+    }
+
+    // runs on resolved statements, so code that gets dropped has still had its
+    // static errors reported; the result is resolved again since folding changes
+    // the expressions the interpreter looks up
+    fn optimize(&self, stmts: Vec<Stmt>) -> Result<Vec<Stmt>, String> {
+        if !self.optimize {
+            return Ok(stmts);
+        }
+        let stmts = Optimizer::new().optimize(stmts);
+        self.resolve(&stmts)?;
+        Ok(stmts)
+    }
+
+    fn resolve(&self, stmts: &Vec<Stmt>) -> Result<(), String> {
+        Resolver::new(&self.interpreter).resolve(stmts)
     }
 }
@@ -0,0 +1,246 @@
+use super::{
+    expr::Expr, generator::yields, interpreter::Interpreter, stmt::Stmt, token::Token,
+    token_type::TokenType, value::Value,
+};
+
+// rewrites the tree before it is resolved for running: operators on literals are
+// folded into a literal, and branches and loops that can never run are dropped
+pub struct Optimizer {
+    // folding evaluates with a real interpreter, so folded values match what
+    // running the program would give
+    interpreter: Interpreter,
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    pub fn optimize(&mut self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        stmts
+            .into_iter()
+            .filter_map(|stmt| self.statement(stmt))
+            .collect()
+    }
+
+    // None when the statement can never do anything
+    fn statement(&mut self, stmt: Stmt) -> Option<Stmt> {
+        match stmt {
+            Stmt::Block(token, stmts) => Some(Stmt::Block(token, self.optimize(stmts))),
+            Stmt::Expression(token, expr) => {
+                Some(Stmt::Expression(token, Box::from(self.expression(*expr))))
+            }
+            Stmt::Print(token, expr) => Some(Stmt::Print(token, Box::from(self.expression(*expr)))),
+            Stmt::Return(token, expr) => {
+                Some(Stmt::Return(token, Box::from(self.expression(*expr))))
+            }
+            Stmt::Yield(token, expr) => Some(Stmt::Yield(token, Box::from(self.expression(*expr)))),
+            Stmt::Break(_) | Stmt::Continue(_) => Some(stmt),
+            Stmt::VariableDeclaration(name, annotation, initializer) => {
+                Some(Stmt::VariableDeclaration(
+                    name,
+                    annotation,
+                    Box::from(self.expression(*initializer)),
+                ))
+            }
+            Stmt::If(keyword, condition, then_branch, else_branch) => {
+                let condition = self.expression(*condition);
+                match truthiness(&condition) {
+                    Some(true) => self.statement(*then_branch),
+                    Some(false) => else_branch.and_then(|else_branch| self.statement(else_branch)),
+                    None => {
+                        let then_branch = self.branch(*then_branch, &keyword);
+                        let else_branch =
+                            else_branch.map(|else_branch| self.branch(else_branch, &keyword));
+                        Some(Stmt::If(
+                            keyword,
+                            Box::from(condition),
+                            Box::from(then_branch),
+                            Box::from(else_branch),
+                        ))
+                    }
+                }
+            }
+            Stmt::While(keyword, condition, body) => {
+                let condition = self.expression(*condition);
+                if truthiness(&condition) == Some(false) {
+                    return None;
+                }
+                let body = self.branch(*body, &keyword);
+                Some(Stmt::While(keyword, Box::from(condition), Box::from(body)))
+            }
+            Stmt::ForIn(keyword, name, iterable, body) => {
+                let iterable = self.expression(*iterable);
+                let body = self.branch(*body, &keyword);
+                Some(Stmt::ForIn(
+                    keyword,
+                    name,
+                    Box::from(iterable),
+                    Box::from(body),
+                ))
+            }
+            Stmt::FunctionDeclaration(name, parameters, body, signature) => {
+                // dropping a generator's last yield would make it a plain function
+                let optimized = self.optimize(body.clone());
+                let body = if yields(&body) && !yields(&optimized) {
+                    body
+                } else {
+                    optimized
+                };
+                Some(Stmt::FunctionDeclaration(name, parameters, body, signature))
+            }
+            Stmt::ClassDeclaration(name, superclass, traits, methods) => Some(
+                Stmt::ClassDeclaration(name, superclass, traits, self.optimize(methods)),
+            ),
+            Stmt::TraitDeclaration(name, methods) => {
+                Some(Stmt::TraitDeclaration(name, self.optimize(methods)))
+            }
+        }
+    }
+
+    // branches need some statement, an empty block stands in for one that was dropped
+    fn branch(&mut self, stmt: Stmt, keyword: &Token) -> Stmt {
+        self.statement(stmt)
+            .unwrap_or_else(|| Stmt::Block(keyword.clone(), Vec::new()))
+    }
+
+    fn expression(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Binary(left, operator, right) => {
+                let folded = Expr::Binary(
+                    Box::from(self.expression(*left)),
+                    operator,
+                    Box::from(self.expression(*right)),
+                );
+                self.fold(folded)
+            }
+            Expr::Urnary(operator, right) => {
+                let folded = Expr::Urnary(operator, Box::from(self.expression(*right)));
+                self.fold(folded)
+            }
+            Expr::Grouping(inner) => {
+                let inner = self.expression(*inner);
+                if is_literal(&inner) {
+                    inner
+                } else {
+                    Expr::Grouping(Box::from(inner))
+                }
+            }
+            Expr::Logical(left, operator, right) => {
+                let left = self.expression(*left);
+                let right = self.expression(*right);
+                // the left side decides whether the right side is ever looked at
+                match (truthiness(&left), &operator.token_type) {
+                    (Some(_), TokenType::QuestionQuestion) if left != Expr::NilLiteral => left,
+                    (Some(true), TokenType::Or) | (Some(false), TokenType::And) => left,
+                    (Some(_), _) => right,
+                    (None, _) => Expr::Logical(Box::from(left), operator, Box::from(right)),
+                }
+            }
+            Expr::Assign(name, value) => Expr::Assign(name, Box::from(self.expression(*value))),
+            Expr::CompoundAssign(name, operator, value) => {
+                Expr::CompoundAssign(name, operator, Box::from(self.expression(*value)))
+            }
+            Expr::CompoundSet(object, name, operator, value) => Expr::CompoundSet(
+                Box::from(self.expression(*object)),
+                name,
+                operator,
+                Box::from(self.expression(*value)),
+            ),
+            Expr::Postfix(update, operator) => {
+                Expr::Postfix(Box::from(self.expression(*update)), operator)
+            }
+            Expr::Await(keyword, task) => Expr::Await(keyword, Box::from(self.expression(*task))),
+            Expr::Spawn(keyword, call) => Expr::Spawn(keyword, Box::from(self.expression(*call))),
+            Expr::Call(callee, paren, arguments) => Expr::Call(
+                Box::from(self.expression(*callee)),
+                paren,
+                arguments
+                    .into_iter()
+                    .map(|argument| self.expression(argument))
+                    .collect(),
+            ),
+            Expr::Conditional(condition, question, then_branch, else_branch) => {
+                let condition = self.expression(*condition);
+                match truthiness(&condition) {
+                    Some(true) => self.expression(*then_branch),
+                    Some(false) => self.expression(*else_branch),
+                    None => Expr::Conditional(
+                        Box::from(condition),
+                        question,
+                        Box::from(self.expression(*then_branch)),
+                        Box::from(self.expression(*else_branch)),
+                    ),
+                }
+            }
+            Expr::Index(object, bracket, index) => Expr::Index(
+                Box::from(self.expression(*object)),
+                bracket,
+                Box::from(self.expression(*index)),
+            ),
+            Expr::Get(object, name) => Expr::Get(Box::from(self.expression(*object)), name),
+            Expr::OptionalGet(object, name) => {
+                Expr::OptionalGet(Box::from(self.expression(*object)), name)
+            }
+            Expr::Set(object, name, value) => Expr::Set(
+                Box::from(self.expression(*object)),
+                name,
+                Box::from(self.expression(*value)),
+            ),
+            Expr::StringLiteral(_)
+            | Expr::NumberLiteral(..)
+            | Expr::IntLiteral(..)
+            | Expr::NilLiteral
+            | Expr::TrueLiteral
+            | Expr::FalseLiteral
+            | Expr::Variable(_)
+            | Expr::This(_)
+            | Expr::Super(_, _) => expr,
+        }
+    }
+
+    // an operator whose operands are all literals becomes its result, unless
+    // evaluating it fails, in which case the error is left for run time
+    fn fold(&mut self, expr: Expr) -> Expr {
+        let operands_are_literals = match &expr {
+            Expr::Binary(left, _, right) => is_literal(left) && is_literal(right),
+            Expr::Urnary(_, right) => is_literal(right),
+            _ => false,
+        };
+        if !operands_are_literals {
+            return expr;
+        }
+        match self.interpreter.evaluate_expression(expr.clone()) {
+            Ok(Value::Number(number)) => Expr::NumberLiteral(number, None),
+            Ok(Value::Int(int)) => Expr::IntLiteral(int, None),
+            Ok(Value::String(string)) => Expr::StringLiteral(string),
+            Ok(Value::Bool(true)) => Expr::TrueLiteral,
+            Ok(Value::Bool(false)) => Expr::FalseLiteral,
+            Ok(Value::Nil) => Expr::NilLiteral,
+            _ => expr,
+        }
+    }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::StringLiteral(_)
+            | Expr::NumberLiteral(..)
+            | Expr::IntLiteral(..)
+            | Expr::NilLiteral
+            | Expr::TrueLiteral
+            | Expr::FalseLiteral
+    )
+}
+
+// whether a literal is truthy, None for anything only known at run time
+fn truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::NilLiteral | Expr::FalseLiteral => Some(false),
+        _ if is_literal(expr) => Some(true),
+        _ => None,
+    }
+}
@@ -1,12 +1,16 @@
 use std::cell::{Cell, RefCell};
 
-use super::{stmt::Stmt, token_type::TokenType};
+use super::{
+    stmt::{MethodKind, Signature, Stmt},
+    token_type::TokenType,
+};
 
 use super::{expr::Expr, token::Literal, token::Token};
 
 pub struct Parser {
     tokens: RefCell<Vec<Token>>,
     current: Cell<usize>,
+    unexpected_eof: Cell<bool>,
 }
 
 impl Parser {
@@ -14,24 +18,46 @@ impl Parser {
         Self {
             tokens: RefCell::new(tokens),
             current: Cell::new(0),
+            unexpected_eof: Cell::new(false),
         }
     }
 
     pub fn parse(&self) -> Result<Vec<Stmt>, String> {
         let mut statements = Vec::new();
+        let mut errors = Vec::new();
         while !self.is_at_end() {
-            let declaration = self.declaration();
-            if declaration.is_ok() {
-                statements.push(declaration.unwrap());
-            } else {
-                println!("{}", declaration.unwrap_err());
+            match self.declaration() {
+                Ok(declaration) => statements.push(declaration),
+                Err(err) => errors.push(err),
             }
         }
-        Ok(statements)
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    // parses the tokens as one expression with no trailing ';', used by the REPL
+    pub fn parse_expression(&self) -> Result<Expr, String> {
+        let expr = self.expression()?;
+        if !self.is_at_end() {
+            let errored_token = self.peek();
+            return Err(format!(
+                "Line {}: Found an unexpected token \"{}\" with type {:?}, Expected end of expression",
+                errored_token.line, errored_token.lexeme, errored_token.token_type
+            ));
+        }
+        Ok(expr)
+    }
+
+    // true if parsing failed because the tokens ran out, i.e. more input could fix it
+    pub fn hit_unexpected_eof(&self) -> bool {
+        self.unexpected_eof.get()
     }
 
     fn declaration(&self) -> Result<Stmt, String> {
-        // declaration     → classDecl | funDecl | varDecl | statement ;
+        // declaration     → classDecl | traitDecl | funDecl | varDecl | statement ;
         if self.match_token_types(vec![TokenType::Class]) {
             match self.class_declaration() {
                 Ok(class_declaration) => Ok(class_declaration),
@@ -40,6 +66,14 @@ impl Parser {
                     Err(err)
                 }
             }
+        } else if self.match_token_types(vec![TokenType::Trait]) {
+            match self.trait_declaration() {
+                Ok(trait_declaration) => Ok(trait_declaration),
+                Err(err) => {
+                    self.synchronize();
+                    Err(err)
+                }
+            }
         } else if self.match_token_types(vec![TokenType::Fun]) {
             match self.func_declaration("function") {
                 Ok(func_declaration) => Ok(func_declaration),
@@ -76,15 +110,124 @@ impl Parser {
             superclass = Some(Expr::Variable(self.previous()));
         }
 
+        let mut traits = Vec::new();
+        if self.match_token_types(vec![TokenType::With]) {
+            loop {
+                self.consume(TokenType::Identifier, "Expected trait name")?;
+                traits.push(Expr::Variable(self.previous()));
+                if !self.match_token_types(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
         self.consume(TokenType::LeftBrace, "Expected '{' before class body")?;
 
-        let mut methods = Vec::new();
+        let mut members = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            methods.push(self.func_declaration("method")?);
+            if self.match_token_types(vec![TokenType::Var]) {
+                members.push(self.field_declaration()?);
+            } else {
+                members.push(self.method_declaration()?);
+            }
         }
 
         self.consume(TokenType::RightBrace, "Expected '}' after class body")?;
-        Ok(Stmt::ClassDeclaration(Box::from(name), superclass, methods))
+        Ok(Stmt::ClassDeclaration(
+            Box::from(name),
+            superclass,
+            traits,
+            members,
+        ))
+    }
+
+    fn trait_declaration(&self) -> Result<Stmt, String> {
+        // traitDecl      → "trait" IDENTIFIER "{" ( field | method )* "}" ;
+        let name = self.consume(TokenType::Identifier, "Expected trait name")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' before trait body")?;
+
+        let mut members = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token_types(vec![TokenType::Var]) {
+                members.push(self.field_declaration()?);
+            } else {
+                members.push(self.method_declaration()?);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expected '}' after trait body")?;
+        Ok(Stmt::TraitDeclaration(Box::from(name), members))
+    }
+
+    fn method_declaration(&self) -> Result<Stmt, String> {
+        // method         → "class" function
+        //                | IDENTIFIER ( ":" type )? block
+        //                | "set" function
+        //                | function ;
+        if self.match_token_types(vec![TokenType::Class]) {
+            return with_kind(self.func_declaration("static method")?, MethodKind::Static);
+        }
+        let name = self.peek();
+        let next = self.peek_next();
+        // "set" is only special when a name follows, so a method can still be called set
+        if name.lexeme == "set" && next.token_type == TokenType::Identifier {
+            self.advance();
+            let setter = with_kind(self.func_declaration("setter")?, MethodKind::Setter)?;
+            if let Stmt::FunctionDeclaration(name, parameters, _, _) = &setter {
+                if parameters.len() != 1 {
+                    return Err(format!(
+                        "Line {}: Setter '{}' must take exactly one parameter",
+                        name.line, name.lexeme
+                    ));
+                }
+            }
+            return Ok(setter);
+        }
+        if name.token_type == TokenType::Identifier
+            && matches!(next.token_type, TokenType::LeftBrace | TokenType::Colon)
+        {
+            self.advance();
+            let signature = Signature {
+                returns: self.type_annotation()?,
+                kind: MethodKind::Getter,
+                ..Signature::default()
+            };
+            self.consume(TokenType::LeftBrace, "Expected '{' before getter body")?;
+            let body = self.block()?;
+            return Ok(Stmt::FunctionDeclaration(name, Vec::new(), body, signature));
+        }
+        self.func_declaration("method")
+    }
+
+    fn field_declaration(&self) -> Result<Stmt, String> {
+        // field          → "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";" ;
+        let name = self.consume(TokenType::Identifier, "Expected field name")?;
+        let annotation = self.type_annotation()?;
+        let initializer = if self.match_token_types(vec![TokenType::Equal]) {
+            self.expression()?
+        } else {
+            Expr::NilLiteral
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after field declaration")?;
+        Ok(Stmt::VariableDeclaration(
+            Box::from(name),
+            annotation,
+            Box::from(initializer),
+        ))
+    }
+
+    // the optional ": type" after a name, the type is a class name or one of
+    // num, str, bool, nil, fun and any
+    fn type_annotation(&self) -> Result<Option<Token>, String> {
+        if !self.match_token_types(vec![TokenType::Colon]) {
+            return Ok(None);
+        }
+        if self.match_token_types(vec![TokenType::Identifier, TokenType::Nil, TokenType::Fun]) {
+            Ok(Some(self.previous()))
+        } else {
+            self.consume(TokenType::Identifier, "Expected type after ':'")
+                .map(Some)
+        }
     }
 
     fn func_declaration(&self, kind: &str) -> Result<Stmt, String> {
@@ -95,6 +238,7 @@ impl Parser {
         )?;
 
         let mut parameters = Vec::new();
+        let mut signature = Signature::default();
         if !self.check(TokenType::RightParen) {
             while {
                 if parameters.len() >= 255 {
@@ -104,25 +248,25 @@ impl Parser {
                     ));
                 }
                 parameters.push(self.consume(TokenType::Identifier, "Expected parameter name")?);
+                signature.parameters.push(self.type_annotation()?);
                 self.match_token_types(vec![TokenType::Comma])
             } {}
         }
 
-        self.consume(
-            TokenType::RightParen,
-            &format!("Expected ')' after parameters"),
-        )?;
+        self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+        signature.returns = self.type_annotation()?;
         self.consume(
             TokenType::LeftBrace,
             &format!("Expected '{{' before {} body", kind),
         )?;
         let body = self.block()?;
-        Ok(Stmt::FunctionDeclaration(name, parameters, body))
+        Ok(Stmt::FunctionDeclaration(name, parameters, body, signature))
     }
 
     fn var_declaration(&self) -> Result<Stmt, String> {
-        // varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+        // varDecl        → "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";" ;
         let name = self.consume(TokenType::Identifier, "Expected variable name")?;
+        let annotation = self.type_annotation()?;
 
         let initializer = if self.match_token_types(vec![TokenType::Equal]) {
             self.expression()?
@@ -137,16 +281,20 @@ impl Parser {
 
         Ok(Stmt::VariableDeclaration(
             Box::from(name),
+            annotation,
             Box::from(initializer),
         ))
     }
 
     fn statement(&self) -> Result<Stmt, String> {
         // statement      → exprStatement
+        //                | breakStatement
+        //                | continueStatement
         //                | forStatement
         //                | ifStatement
         //                | printStatement
         //                | whileStatement
+        //                | yieldStatement
         //                | block;
         if self.match_token_types(vec![TokenType::If]) {
             Ok(self.if_statement()?)
@@ -158,8 +306,21 @@ impl Parser {
             Ok(self.return_statement()?)
         } else if self.match_token_types(vec![TokenType::While]) {
             Ok(self.while_statement()?)
+        } else if self.match_token_types(vec![TokenType::Yield]) {
+            Ok(self.yield_statement()?)
+        } else if self.match_token_types(vec![TokenType::Break]) {
+            // breakStatement → "break" ";" ;
+            let keyword = self.previous();
+            self.consume(TokenType::Semicolon, "Expected ';' after 'break'")?;
+            Ok(Stmt::Break(keyword))
+        } else if self.match_token_types(vec![TokenType::Continue]) {
+            // continueStatement → "continue" ";" ;
+            let keyword = self.previous();
+            self.consume(TokenType::Semicolon, "Expected ';' after 'continue'")?;
+            Ok(Stmt::Continue(keyword))
         } else if self.match_token_types(vec![TokenType::LeftBrace]) {
-            Ok(Stmt::Block(self.block()?))
+            let brace = self.previous();
+            Ok(Stmt::Block(brace, self.block()?))
         } else {
             Ok(self.expression_statement()?)
         }
@@ -179,13 +340,15 @@ impl Parser {
 
     fn expression_statement(&self) -> Result<Stmt, String> {
         // exprStatement  → expression ";";
+        let first = self.peek();
         let expression = self.expression()?;
         self.consume(TokenType::Semicolon, "Expected ';' after value")?;
-        Ok(Stmt::Expression(Box::from(expression)))
+        Ok(Stmt::Expression(first, Box::from(expression)))
     }
 
     fn if_statement(&self) -> Result<Stmt, String> {
         // ifStatement    → "if" "(" expression ")" statement ( "else" statement )?;
+        let keyword = self.previous();
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after 'if' condition.")?;
@@ -199,6 +362,7 @@ impl Parser {
         };
 
         Ok(Stmt::If(
+            keyword,
             Box::from(condition),
             Box::from(then_branch),
             Box::from(else_branch),
@@ -210,8 +374,25 @@ impl Parser {
         //                  "(" (varDecl | exprStatement | ";")
         //                  expression? ";"
         //                  expression? ")"
-        //                  statement;
+        //                  statement
+        //                | "for" "(" "var" IDENTIFIER "in" expression ")" statement ;
+        // the desugared statements all carry the 'for' keyword so the loop can be recognized later
+        let keyword = self.previous();
         self.consume(TokenType::LeftParen, "Expected '(' after 'for'")?;
+        if self.is_for_in() {
+            self.advance();
+            let name = self.advance();
+            self.advance();
+            let iterable = self.expression()?;
+            self.consume(TokenType::RightParen, "Expected ')' after for-in clause")?;
+            let body = self.statement()?;
+            return Ok(Stmt::ForIn(
+                keyword,
+                Box::from(name),
+                Box::from(iterable),
+                Box::from(body),
+            ));
+        }
         let initializer = if self.match_token_types(vec![TokenType::Semicolon]) {
             None
         } else if self.match_token_types(vec![TokenType::Var]) {
@@ -237,20 +418,23 @@ impl Parser {
         let mut body = self.statement()?;
 
         if let Some(statement) = increment {
-            body = Stmt::Block(vec![body, Stmt::Expression(Box::from(statement))]);
+            body = Stmt::Block(
+                keyword.clone(),
+                vec![
+                    body,
+                    Stmt::Expression(keyword.clone(), Box::from(statement)),
+                ],
+            );
         }
 
         body = Stmt::While(
-            if let None = condition {
-                Box::from(Expr::TrueLiteral)
-            } else {
-                Box::from(condition.unwrap())
-            },
+            keyword.clone(),
+            Box::from(condition.unwrap_or(Expr::TrueLiteral)),
             Box::from(body),
         );
 
         if let Some(statement) = initializer {
-            body = Stmt::Block(vec![statement, body]);
+            body = Stmt::Block(keyword, vec![statement, body]);
         }
 
         Ok(body)
@@ -258,9 +442,10 @@ impl Parser {
 
     fn print_statement(&self) -> Result<Stmt, String> {
         // printStatement → "print" expression ";";
+        let keyword = self.previous();
         let expression = self.expression()?;
         self.consume(TokenType::Semicolon, "Expected ';' after value")?;
-        Ok(Stmt::Print(Box::from(expression)))
+        Ok(Stmt::Print(keyword, Box::from(expression)))
     }
 
     fn return_statement(&self) -> Result<Stmt, String> {
@@ -274,13 +459,22 @@ impl Parser {
         Ok(Stmt::Return(keyword, Box::new(value)))
     }
 
+    fn yield_statement(&self) -> Result<Stmt, String> {
+        // yieldStatement → "yield" expression ";" ;
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after yield value")?;
+        Ok(Stmt::Yield(keyword, Box::new(value)))
+    }
+
     fn while_statement(&self) -> Result<Stmt, String> {
         // whileStatement → "while" "(" expression ")" statement;
+        let keyword = self.previous();
         self.consume(TokenType::LeftParen, "Expected '(' after 'while'")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expected ')' after 'while'")?;
         let body = self.statement()?;
-        Ok(Stmt::While(Box::from(condition), Box::from(body)))
+        Ok(Stmt::While(keyword, Box::from(condition), Box::from(body)))
     }
 
     fn expression(&self) -> Result<Expr, String> {
@@ -289,20 +483,61 @@ impl Parser {
     }
 
     fn assignment(&self) -> Result<Expr, String> {
-        // assignment      → ( call "." )? IDENTIFIER "=" assignment
-        //                 | logic_or ;
-        let expr = self.or()?;
+        // assignment      → ( call "." )? IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) assignment
+        //                 | conditional ;
+        let expr = self.conditional()?;
         if self.match_token_types(vec![TokenType::Equal]) {
             let equals = self.previous();
             let value = self.assignment()?;
             match expr {
                 Expr::Variable(name) => return Ok(Expr::Assign(name, Box::from(value))),
-                Expr::Get(object, name) => {
-                    return Ok(Expr::Set(Box::from(object), name, Box::from(value)))
-                }
+                Expr::Get(object, name) => return Ok(Expr::Set(object, name, Box::from(value))),
                 _ => return Err(format!("Invalid assignment: {} {} {}", expr, equals, value)),
             }
         };
+        if self.match_token_types(vec![
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PercentEqual,
+        ]) {
+            let operator = self.previous();
+            let value = self.assignment()?;
+            return compound(expr, operator, value);
+        }
+        Ok(expr)
+    }
+
+    fn conditional(&self) -> Result<Expr, String> {
+        // conditional    → coalesce ( "?" expression ":" conditional )? ;
+        let expr = self.coalesce()?;
+        if self.match_token_types(vec![TokenType::Question]) {
+            let question = self.previous();
+            let then_branch = self.expression()?;
+            self.consume(TokenType::Colon, "Expected ':' after then branch of '?'")?;
+            // recursing on the else branch makes it right-associative
+            let else_branch = self.conditional()?;
+            return Ok(Expr::Conditional(
+                Box::from(expr),
+                question,
+                Box::from(then_branch),
+                Box::from(else_branch),
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn coalesce(&self) -> Result<Expr, String> {
+        // coalesce       → logic_or ( "??" logic_or )* ;
+        let mut expr = self.or()?;
+
+        while self.match_token_types(vec![TokenType::QuestionQuestion]) {
+            let operator = self.previous();
+            let right = self.or()?;
+            expr = Expr::Logical(Box::from(expr), operator, Box::from(right));
+        }
+
         Ok(expr)
     }
 
@@ -346,15 +581,68 @@ impl Parser {
     }
 
     fn comparison(&self) -> Result<Expr, String> {
-        // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
-        let mut expr = self.term()?;
+        // comparison     → bit_or ( ( ">" | ">=" | "<" | "<=" | "is" ) bit_or )* ;
+        let mut expr = self.bit_or()?;
 
         while self.match_token_types(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::Is,
         ]) {
+            let operator = self.previous();
+            let right = self.bit_or()?;
+            expr = Expr::Binary(Box::from(expr), operator, Box::from(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_or(&self) -> Result<Expr, String> {
+        // bit_or         → bit_xor ( "|" bit_xor )* ;
+        let mut expr = self.bit_xor()?;
+
+        while self.match_token_types(vec![TokenType::Pipe]) {
+            let operator = self.previous();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary(Box::from(expr), operator, Box::from(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&self) -> Result<Expr, String> {
+        // bit_xor        → bit_and ( "^" bit_and )* ;
+        let mut expr = self.bit_and()?;
+
+        while self.match_token_types(vec![TokenType::Caret]) {
+            let operator = self.previous();
+            let right = self.bit_and()?;
+            expr = Expr::Binary(Box::from(expr), operator, Box::from(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&self) -> Result<Expr, String> {
+        // bit_and        → shift ( "&" shift )* ;
+        let mut expr = self.shift()?;
+
+        while self.match_token_types(vec![TokenType::Ampersand]) {
+            let operator = self.previous();
+            let right = self.shift()?;
+            expr = Expr::Binary(Box::from(expr), operator, Box::from(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn shift(&self) -> Result<Expr, String> {
+        // shift          → term ( ( "<<" | ">>" ) term )* ;
+        let mut expr = self.term()?;
+
+        while self.match_token_types(vec![TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.previous();
             let right = self.term()?;
             expr = Expr::Binary(Box::from(expr), operator, Box::from(right));
@@ -377,10 +665,15 @@ impl Parser {
     }
 
     fn factor(&self) -> Result<Expr, String> {
-        // factor         → unary ( ( "/" | "*" ) unary )* ; // instead of making it left-recursive, we make it a flat sequence of mults/divs
+        // factor         → unary ( ( "/" | "\\" | "*" | "%" ) unary )* ; // instead of making it left-recursive, we make it a flat sequence of mults/divs
         let mut expr = self.urnary()?;
 
-        while self.match_token_types(vec![TokenType::Star, TokenType::Slash]) {
+        while self.match_token_types(vec![
+            TokenType::Star,
+            TokenType::Slash,
+            TokenType::Backslash,
+            TokenType::Percent,
+        ]) {
             let operator = self.previous();
             let right = self.urnary()?;
             expr = Expr::Binary(Box::from(expr), operator, Box::from(right));
@@ -390,19 +683,61 @@ impl Parser {
     }
 
     fn urnary(&self) -> Result<Expr, String> {
-        // unary          → ( "!" | "-" ) unary // recursive urnary
-        //                | call ;
-        if self.match_token_types(vec![TokenType::Bang, TokenType::Minus]) {
+        // unary          → ( "!" | "-" | "~" | "await" ) unary // recursive urnary
+        //                | ( "++" | "--" ) unary
+        //                | "spawn" call
+        //                | exponent ;
+        if self.match_token_types(vec![TokenType::Bang, TokenType::Minus, TokenType::Tilde]) {
             let operator = self.previous();
             let right = self.urnary()?;
             Ok(Expr::Urnary(operator, Box::from(right)))
+        } else if self.match_token_types(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous();
+            let target = self.urnary()?;
+            compound(target, operator, Expr::IntLiteral(1, None))
+        } else if self.match_token_types(vec![TokenType::Await]) {
+            let keyword = self.previous();
+            let task = self.urnary()?;
+            Ok(Expr::Await(keyword, Box::from(task)))
+        } else if self.match_token_types(vec![TokenType::Spawn]) {
+            let keyword = self.previous();
+            match self.call()? {
+                call @ Expr::Call(_, _, _) => Ok(Expr::Spawn(keyword, Box::from(call))),
+                other => Err(format!(
+                    "Line {}: Expected a call after 'spawn': {}",
+                    keyword.line, other
+                )),
+            }
         } else {
-            self.call()
+            self.exponent()
         }
     }
 
+    fn exponent(&self) -> Result<Expr, String> {
+        // exponent       → postfix ( "**" unary )? ; // right-associative, and "-2 ** 2" is -(2 ** 2)
+        let expr = self.postfix()?;
+        if self.match_token_types(vec![TokenType::StarStar]) {
+            let operator = self.previous();
+            let right = self.urnary()?;
+            return Ok(Expr::Binary(Box::from(expr), operator, Box::from(right)));
+        }
+        Ok(expr)
+    }
+
+    fn postfix(&self) -> Result<Expr, String> {
+        // postfix        → call ( "++" | "--" )? ;
+        let expr = self.call()?;
+        if self.match_token_types(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous();
+            let update = compound(expr, operator.clone(), Expr::IntLiteral(1, None))?;
+            return Ok(Expr::Postfix(Box::from(update), operator));
+        }
+        Ok(expr)
+    }
+
     fn call(&self) -> Result<Expr, String> {
-        // call            → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+        // call            → primary ( "(" arguments? ")" | "[" expression "]"
+        //                   | ( "." | "?." ) IDENTIFIER )* ;
         let mut expr = self.primary()?;
         loop {
             if self.match_token_types(vec![TokenType::LeftParen]) {
@@ -424,11 +759,21 @@ impl Parser {
                 let paren = self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
 
                 expr = Expr::Call(Box::from(expr), paren, arguments);
+            } else if self.match_token_types(vec![TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+
+                expr = Expr::Index(Box::from(expr), bracket, Box::from(index));
             } else if self.match_token_types(vec![TokenType::Dot]) {
                 let name =
                     self.consume(TokenType::Identifier, "Expected property name after '.'")?;
 
                 expr = Expr::Get(Box::from(expr), name);
+            } else if self.match_token_types(vec![TokenType::QuestionDot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expected property name after '?.'")?;
+
+                expr = Expr::OptionalGet(Box::from(expr), name);
             } else {
                 break;
             }
@@ -447,10 +792,13 @@ impl Parser {
         } else if self.match_token_types(vec![TokenType::Nil]) {
             Ok(Expr::NilLiteral)
         } else if self.match_token_types(vec![TokenType::Number]) {
-            if let Literal::Number(number_literal) = self.previous().literal.unwrap() {
-                Ok(Expr::NumberLiteral(number_literal))
-            } else {
-                Err("Expected number literal".to_string())
+            let number = self.previous();
+            match number.literal.unwrap() {
+                Literal::Number(number_literal) => {
+                    Ok(Expr::NumberLiteral(number_literal, Some(number.lexeme)))
+                }
+                Literal::Int(int_literal) => Ok(Expr::IntLiteral(int_literal, Some(number.lexeme))),
+                _ => Err("Expected number literal".to_string()),
             }
         } else if self.match_token_types(vec![TokenType::String]) {
             if let Literal::String(string_literal) = self.previous().literal.unwrap() {
@@ -473,6 +821,7 @@ impl Parser {
             Ok(Expr::Grouping(Box::new(expr)))
         } else {
             let errored_token = self.peek();
+            self.note_unexpected(&errored_token);
             Err(format!(
                 "Line {}: Found an unexpected token \"{}\" with type {:?}",
                 errored_token.line, errored_token.lexeme, errored_token.token_type,
@@ -485,6 +834,7 @@ impl Parser {
             Ok(self.advance())
         } else {
             let errored_token = self.peek();
+            self.note_unexpected(&errored_token);
             Err(format!(
                 "Line {}: Found an unexpected token \"{}\" with type {:?}, {}",
                 errored_token.line, errored_token.lexeme, errored_token.token_type, message
@@ -492,6 +842,12 @@ impl Parser {
         }
     }
 
+    fn note_unexpected(&self, errored_token: &Token) {
+        if errored_token.token_type == TokenType::Eof {
+            self.unexpected_eof.set(true);
+        }
+    }
+
     fn match_token_types(&self, token_types: Vec<TokenType>) -> bool {
         for token_type in token_types {
             if self.check(token_type) {
@@ -525,6 +881,29 @@ impl Parser {
         self.tokens.to_owned().into_inner()[self.current.get()].clone()
     }
 
+    // whether the next tokens are "var" IDENTIFIER "in"
+    fn is_for_in(&self) -> bool {
+        let tokens = self.tokens.borrow();
+        let current = self.current.get();
+        match tokens.get(current..current + 3) {
+            Some([var, name, keyword]) => {
+                var.token_type == TokenType::Var
+                    && name.token_type == TokenType::Identifier
+                    && keyword.token_type == TokenType::In
+            }
+            _ => false,
+        }
+    }
+
+    fn peek_next(&self) -> Token {
+        // the token after the current one, or the end of the file
+        let tokens = self.tokens.borrow();
+        tokens
+            .get(self.current.get() + 1)
+            .unwrap_or_else(|| tokens.last().unwrap())
+            .clone()
+    }
+
     fn previous(&self) -> Token {
         // returns previous token we just consumed
         self.tokens.to_owned().into_inner()[self.current.get() - 1].clone()
@@ -541,13 +920,15 @@ impl Parser {
 
             match self.peek().token_type {
                 TokenType::Class
+                | TokenType::Trait
                 | TokenType::Fun
                 | TokenType::Var
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => {
+                | TokenType::Return
+                | TokenType::Yield => {
                     return;
                 }
                 _ => (),
@@ -557,3 +938,24 @@ impl Parser {
         }
     }
 }
+
+// builds the update for "+=" and friends or "++"/"--", which can only target what "=" can
+fn compound(target: Expr, operator: Token, value: Expr) -> Result<Expr, String> {
+    match target {
+        Expr::Variable(name) => Ok(Expr::CompoundAssign(name, operator, Box::from(value))),
+        Expr::Get(object, name) => Ok(Expr::CompoundSet(object, name, operator, Box::from(value))),
+        _ => Err(format!(
+            "Line {}: Invalid target for '{}': {}",
+            operator.line, operator.lexeme, target
+        )),
+    }
+}
+
+fn with_kind(function: Stmt, kind: MethodKind) -> Result<Stmt, String> {
+    match function {
+        Stmt::FunctionDeclaration(name, parameters, body, signature) => Ok(
+            Stmt::FunctionDeclaration(name, parameters, body, Signature { kind, ..signature }),
+        ),
+        _ => Err(format!("Expected a function, got {}", function)),
+    }
+}
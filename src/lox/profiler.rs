@@ -0,0 +1,232 @@
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::HashMap,
+    fs,
+    process::exit,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use super::{
+    interpreter::{Hook, Interpreter},
+    stmt::Stmt,
+    Lox,
+};
+
+#[derive(Default)]
+struct Stats {
+    count: usize,
+    inclusive: Duration,
+    exclusive: Duration,
+    // how many times this is on the stack, only the outermost adds inclusive time
+    active: usize,
+}
+
+impl Stats {
+    fn enter(&mut self) {
+        self.count += 1;
+        self.active += 1;
+    }
+
+    fn exit(&mut self, started: Instant, ended: Instant) {
+        self.active -= 1;
+        if self.active == 0 {
+            self.inclusive += ended - started;
+        }
+    }
+}
+
+// time is charged to whatever is innermost between consecutive events,
+// which gives exclusive times; inclusive times come from start to end, both
+// measured at the same events so exclusive time never exceeds inclusive
+struct Profiler {
+    last_event: Instant,
+    // statements being run, innermost last
+    statements: Vec<(i32, Instant)>,
+    // calls being run, the script at the bottom
+    calls: Vec<(String, Instant)>,
+    lines: HashMap<i32, Stats>,
+    functions: HashMap<String, Stats>,
+    // exclusive time per call stack, for flamegraphs
+    stacks: HashMap<String, Duration>,
+}
+
+impl Lox {
+    // runs the file, then reports where the time went and writes
+    // the collapsed stacks next to it
+    pub fn profile_file(&mut self, file_name: &str) {
+        let (source, stmts) = self.load_file(file_name);
+
+        let profiler = Rc::new(RefCell::new(Profiler::new()));
+        self.interpreter.set_hook(Box::new(profiler.clone()));
+        let result = self.interpreter.interpret(stmts);
+        let mut profiler = profiler.borrow_mut();
+        profiler.finish();
+
+        // the program's output is on stdout, so the report stays out of its way
+        eprint!("{}", profiler.report(&source));
+        let folded = format!("{}.folded", file_name);
+        match fs::write(&folded, profiler.collapsed_stacks()) {
+            Ok(()) => eprintln!("Collapsed stacks written to {}", folded),
+            Err(err) => eprintln!("Could not write '{}': {}", folded, err),
+        }
+
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            exit(70);
+        }
+    }
+}
+
+impl Profiler {
+    fn new() -> Self {
+        let now = Instant::now();
+        let mut functions = HashMap::new();
+        functions.insert("script".to_string(), Stats::default());
+        functions.get_mut("script").unwrap().enter();
+        Self {
+            last_event: now,
+            statements: Vec::new(),
+            calls: vec![("script".to_string(), now)],
+            lines: HashMap::new(),
+            functions,
+            stacks: HashMap::new(),
+        }
+    }
+
+    // gives the time since the last event to the innermost line, call and stack
+    fn charge(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last_event;
+        self.last_event = now;
+        if let Some((line, _)) = self.statements.last() {
+            self.lines.entry(*line).or_default().exclusive += elapsed;
+        }
+        if let Some((function, _)) = self.calls.last() {
+            self.functions
+                .entry(function.clone())
+                .or_default()
+                .exclusive += elapsed;
+        }
+        let stack = self
+            .calls
+            .iter()
+            .map(|(function, _)| function.as_str())
+            .collect::<Vec<&str>>()
+            .join(";");
+        *self.stacks.entry(stack).or_default() += elapsed;
+    }
+
+    fn finish(&mut self) {
+        self.charge();
+        while let Some((function, started)) = self.calls.pop() {
+            let ended = self.last_event;
+            self.functions
+                .entry(function)
+                .or_default()
+                .exit(started, ended);
+        }
+    }
+
+    fn report(&self, source: &str) -> String {
+        let source_lines = source.lines().collect::<Vec<&str>>();
+        let mut report = String::new();
+
+        report.push_str("Functions by exclusive time\n");
+        report.push_str(&header("function"));
+        for (function, stats) in sorted(&self.functions) {
+            report.push_str(&row(stats, function));
+        }
+
+        report.push_str("\nLines by exclusive time\n");
+        report.push_str(&header("line"));
+        for (line, stats) in sorted(&self.lines) {
+            let text = source_lines
+                .get(*line as usize - 1)
+                .map_or("", |text| text.trim());
+            report.push_str(&row(stats, &format!("{:>4}  {}", line, text)));
+        }
+        report
+    }
+
+    // "script;outer;inner 1234" per line, in microseconds
+    fn collapsed_stacks(&self) -> String {
+        let mut stacks = self
+            .stacks
+            .iter()
+            .map(|(stack, time)| (stack, time.as_micros()))
+            .filter(|(_, micros)| *micros > 0)
+            .collect::<Vec<(&String, u128)>>();
+        stacks.sort();
+        stacks
+            .iter()
+            .map(|(stack, micros)| format!("{} {}\n", stack, micros))
+            .collect()
+    }
+}
+
+impl Hook for Profiler {
+    fn before_statement(&mut self, _interpreter: &mut Interpreter, stmt: &Stmt) {
+        self.charge();
+        let line = stmt.line();
+        self.lines.entry(line).or_default().enter();
+        self.statements.push((line, self.last_event));
+    }
+
+    fn after_statement(&mut self, _interpreter: &mut Interpreter, _stmt: &Stmt) {
+        self.charge();
+        if let Some((line, started)) = self.statements.pop() {
+            let ended = self.last_event;
+            self.lines.entry(line).or_default().exit(started, ended);
+        }
+    }
+
+    fn enter_call(&mut self, interpreter: &mut Interpreter) {
+        self.charge();
+        if let Some(frame) = interpreter.frames.last() {
+            // the line keeps functions with the same name apart
+            let function = format!("{}:{}", frame.name, frame.line);
+            self.functions.entry(function.clone()).or_default().enter();
+            self.calls.push((function, self.last_event));
+        }
+    }
+
+    fn exit_call(&mut self, _interpreter: &mut Interpreter) {
+        self.charge();
+        if let Some((function, started)) = self.calls.pop() {
+            let ended = self.last_event;
+            self.functions
+                .entry(function)
+                .or_default()
+                .exit(started, ended);
+        }
+    }
+}
+
+fn sorted<K>(stats: &HashMap<K, Stats>) -> Vec<(&K, &Stats)> {
+    let mut sorted = stats.iter().collect::<Vec<(&K, &Stats)>>();
+    sorted.sort_by_key(|(_, stats)| Reverse(stats.exclusive));
+    sorted
+}
+
+fn header(name: &str) -> String {
+    format!(
+        "{:>10} {:>12} {:>12}  {}\n",
+        "count", "inclusive", "exclusive", name
+    )
+}
+
+fn row(stats: &Stats, name: &str) -> String {
+    format!(
+        "{:>10} {:>12} {:>12}  {}\n",
+        stats.count,
+        format_duration(stats.inclusive),
+        format_duration(stats.exclusive),
+        name
+    )
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
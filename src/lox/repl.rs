@@ -0,0 +1,215 @@
+use std::{env, fs, path::PathBuf, time::Instant};
+
+use rustyline::{error::ReadlineError, Editor};
+
+use super::{
+    interpreter::Interpreter, parser::Parser, scanner::Scanner, stmt::Stmt, token_type::TokenType,
+    value::Value, Lox,
+};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+const HISTORY_FILE: &str = ".lox_history";
+
+const HELP: &str = "\
+:help           show this message
+:globals        list the global variables
+:ast <code>     print the syntax tree for <code>
+:tokens <code>  print the tokens for <code>
+:load <file>    run <file> in this session
+:reset          forget everything defined so far
+:time <code>    run <code> and report how long it took
+:quit           exit the REPL (ctrl-d works too)";
+
+impl Lox {
+    pub fn run_prompt(&mut self) {
+        let mut editor = Editor::<()>::new();
+        let history_path = history_path();
+        if let Some(path) = &history_path {
+            // a missing history file just means this is the first session
+            let _ = editor.load_history(path);
+        }
+
+        // lines are buffered until they form a complete chunk of source
+        let mut buffer = String::new();
+        loop {
+            let prompt = if buffer.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if buffer.is_empty() && line.trim().is_empty() {
+                        continue;
+                    }
+                    if buffer.is_empty() && line.trim_start().starts_with(':') {
+                        editor.add_history_entry(line.trim());
+                        if !self.run_command(line.trim()) {
+                            break;
+                        }
+                        continue;
+                    }
+                    buffer.push_str(&line);
+                    buffer.push('\n');
+                    if is_incomplete(&buffer) {
+                        continue;
+                    }
+                    editor.add_history_entry(buffer.trim_end());
+                    // run_line borrows buffer
+                    self.run_line(&buffer);
+                    buffer.clear();
+                }
+                // ctrl-c throws away whatever has been typed so far
+                Err(ReadlineError::Interrupted) => buffer.clear(),
+                // ctrl-d exits
+                Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    println!("{}", err);
+                    break;
+                }
+            }
+        }
+
+        if let Some(path) = &history_path {
+            if let Err(err) = editor.save_history(path) {
+                println!("Failed to save history: {}", err);
+            }
+        }
+    }
+
+    // like run, but a bare expression (with or without ';') has its value echoed
+    fn run_line(&mut self, source: &str) {
+        let stmts = match Self::parse_source(source) {
+            Ok(stmts) => stmts,
+            Err(err) => match parse_bare_expression(source) {
+                Some(stmt) => vec![stmt],
+                None => {
+                    println!("{}", err);
+                    return;
+                }
+            },
+        };
+
+        let stmts = match self.analyze(&stmts).and_then(|_| self.optimize(stmts)) {
+            Ok(stmts) => stmts,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+
+        for stmt in stmts {
+            let result = match stmt {
+                Stmt::Expression(_, expr) => match self.interpreter.evaluate_expression(*expr) {
+                    Ok(Value::Nil) => Ok(()),
+                    Ok(value) => {
+                        println!("{}", value);
+                        Ok(())
+                    }
+                    Err(err) => Err(err),
+                },
+                _ => self.interpreter.interpret(vec![stmt]),
+            };
+            if let Err(err) = result {
+                println!("{}", err);
+                return;
+            }
+        }
+    }
+
+    // returns false when the REPL should exit
+    fn run_command(&mut self, line: &str) -> bool {
+        let (command, argument) = match line.find(char::is_whitespace) {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line, ""),
+        };
+        match command {
+            ":help" => println!("{}", HELP),
+            ":quit" => return false,
+            ":globals" => {
+                let globals = self.interpreter.globals.borrow();
+                let mut names = globals.values().keys().collect::<Vec<&String>>();
+                names.sort();
+                for name in names {
+                    println!("{} = {}", name, globals.values()[name]);
+                }
+            }
+            ":ast" => match Self::parse_source(argument) {
+                Ok(stmts) => {
+                    for stmt in stmts {
+                        println!("{}", stmt);
+                    }
+                }
+                Err(err) => match parse_bare_expression(argument) {
+                    Some(Stmt::Expression(_, expr)) => println!("{}", expr),
+                    _ => println!("{}", err),
+                },
+            },
+            ":tokens" => match Scanner::new(argument).scan_tokens() {
+                Ok(tokens) => {
+                    for token in tokens {
+                        println!("{}", token);
+                    }
+                }
+                Err(err) => println!("{}", err),
+            },
+            ":load" => match fs::read_to_string(argument) {
+                Ok(source) => self.run(&source, true),
+                Err(err) => println!("Could not read '{}': {}", argument, err),
+            },
+            ":reset" => {
+                self.interpreter = Interpreter::new();
+                println!("Session reset");
+            }
+            ":time" => {
+                let start = Instant::now();
+                self.run_line(argument);
+                println!("took {:?}", start.elapsed());
+            }
+            _ => println!("Unknown command '{}', try :help", command),
+        }
+        true
+    }
+}
+
+fn parse_bare_expression(source: &str) -> Option<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens().ok()?;
+    let first = tokens[0].clone();
+    let expr = Parser::new(tokens).parse_expression().ok()?;
+    Some(Stmt::Expression(first, Box::from(expr)))
+}
+
+// history lives in the home directory so it is shared between sessions
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// true if the source could still become valid with more lines, e.g. an open
+// brace, an unterminated string, or a statement that stops at the end of input
+fn is_incomplete(source: &str) -> bool {
+    let mut scanner = Scanner::new(source);
+    let tokens = match scanner.scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(_) => return scanner.hit_unterminated_string(),
+    };
+
+    let mut depth = 0;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::LeftBrace | TokenType::LeftParen => depth += 1,
+            TokenType::RightBrace | TokenType::RightParen => depth -= 1,
+            _ => (),
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+
+    // a bare expression is complete even though it lacks its ';'
+    let parser = Parser::new(tokens);
+    parser.parse().is_err()
+        && parser.hit_unexpected_eof()
+        && parse_bare_expression(source).is_none()
+}
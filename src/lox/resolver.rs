@@ -1,11 +1,20 @@
-use std::collections::HashMap;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
 
-use super::{expr::Expr, interpreter::Interpreter, stmt::Stmt, token::Token};
+use super::{
+    expr::Expr,
+    interpreter::Interpreter,
+    stmt::{MethodKind, Stmt},
+    token::Token,
+};
 
 #[derive(Clone, Copy, PartialEq)]
 enum FunctionType {
     Function,
     Method,
+    StaticMethod,
     Initializer,
     None,
 }
@@ -15,13 +24,26 @@ enum ClassType {
     None,
     Class,
     Subclass,
+    Trait,
 }
 
 pub struct Resolver<'a> {
     interpreter: &'a Interpreter,
     scopes: Vec<HashMap<String, bool>>,
+    // the token that declared each name in scopes, for tooling
+    declarations: Vec<HashMap<String, Token>>,
+    // every variable use and the local it resolved to, None for globals
+    references: RefCell<Vec<(Token, Option<Token>)>>,
     current_function: FunctionType,
     current_class: ClassType,
+    // the class whose body is being resolved, whose static members it can use by name
+    class_name: Option<String>,
+    // stays set in functions nested inside a static method, which have no 'this' either
+    in_static_method: bool,
+    // whether break and continue have a loop to go to, functions start outside one
+    in_loop: bool,
+    // set just before resolving an await a task could stop at, see Generator
+    await_allowed: Cell<bool>,
 }
 
 impl<'a> Resolver<'a> {
@@ -29,11 +51,21 @@ impl<'a> Resolver<'a> {
         Self {
             interpreter,
             scopes: Vec::new(),
+            declarations: Vec::new(),
+            references: RefCell::new(Vec::new()),
             current_function: FunctionType::None,
             current_class: ClassType::None,
+            class_name: None,
+            in_static_method: false,
+            in_loop: false,
+            await_allowed: Cell::new(false),
         }
     }
 
+    pub fn references(&self) -> Vec<(Token, Option<Token>)> {
+        self.references.borrow().clone()
+    }
+
     pub fn resolve(&mut self, statements: &Vec<Stmt>) -> Result<(), String> {
         for statement in statements {
             self.resolve_statement(statement)?;
@@ -44,30 +76,40 @@ impl<'a> Resolver<'a> {
     fn resolve_statement(&mut self, statement: &Stmt) -> Result<(), String> {
         // println!("resolving: {}", statement);
         match statement {
-            Stmt::Block(statements) => {
+            Stmt::Block(_, statements) => {
                 self.begin_scope();
                 self.resolve(statements)?;
                 self.end_scope();
             }
-            Stmt::Expression(expression) => {
-                self.resolve_expression(expression)?;
+            Stmt::Expression(_, expression) => {
+                self.resolve_awaitable(expression)?;
             }
-            Stmt::If(condition, then_branch, else_branch) => {
+            Stmt::If(_, condition, then_branch, else_branch) => {
                 self.resolve_expression(condition)?;
                 self.resolve_statement(then_branch)?;
                 if let Some(else_statement) = &**else_branch {
                     self.resolve_statement(else_statement)?;
                 }
             }
-            Stmt::Print(expression) => {
+            Stmt::Print(_, expression) => {
                 self.resolve_expression(expression)?;
             }
-            Stmt::While(condition, statement) => {
+            Stmt::While(_, condition, statement) => {
                 self.resolve_expression(condition)?;
-                self.resolve_statement(statement)?;
+                self.resolve_loop_body(statement)?;
+            }
+            Stmt::ForIn(_, name, iterable, body) => {
+                // the loop variable gets its own scope, matching the environment made for it
+                self.resolve_expression(iterable)?;
+                self.begin_scope();
+                self.declare(name)?;
+                self.define(name);
+                self.resolve_loop_body(body)?;
+                self.end_scope();
             }
-            Stmt::ClassDeclaration(name, superclass, methods) => {
+            Stmt::ClassDeclaration(name, superclass, traits, methods) => {
                 let enclosing_class = self.current_class;
+                let enclosing_name = self.class_name.replace(name.lexeme.clone());
                 self.current_class = ClassType::Class;
 
                 self.declare(name)?;
@@ -77,11 +119,19 @@ impl<'a> Resolver<'a> {
                     self.current_class = ClassType::Subclass;
                     if let Expr::Variable(superclass_name) = superclass {
                         if name.lexeme == superclass_name.lexeme {
-                            return Err("A class can't inherit from itself".to_string());
+                            return Err(error(
+                                superclass_name,
+                                "A class can't inherit from itself",
+                            ));
                         }
                         self.resolve_expression(superclass)?;
                     }
+                }
+                for mixed in traits {
+                    self.resolve_expression(mixed)?;
+                }
 
+                if superclass.is_some() {
                     self.begin_scope();
                     self.scopes
                         .last_mut()
@@ -89,60 +139,88 @@ impl<'a> Resolver<'a> {
                         .insert("super".to_string(), true);
                 }
 
-                self.begin_scope();
-                self.scopes
-                    .last_mut()
-                    .unwrap()
-                    .insert("this".to_string(), true);
-
-                for method in methods {
-                    if let Stmt::FunctionDeclaration(method_name, _, _) = method {
-                        self.resolve_function(
-                            method,
-                            if method_name.lexeme == "init" {
-                                &FunctionType::Initializer
-                            } else {
-                                &FunctionType::Method
-                            },
-                        )?;
-                    }
-                }
-
-                self.end_scope();
+                self.resolve_members(methods)?;
 
-                if let Some(_) = superclass {
+                if superclass.is_some() {
                     self.end_scope();
                 }
                 self.current_class = enclosing_class;
+                self.class_name = enclosing_name;
             }
-            Stmt::VariableDeclaration(name, initializer) => {
+            // trait methods are bound to instances like any other, so they have 'this'
+            Stmt::TraitDeclaration(name, methods) => {
+                let enclosing_class = self.current_class;
+                let enclosing_name = self.class_name.take();
+                self.current_class = ClassType::Trait;
+
+                self.declare(name)?;
+                self.define(name);
+                self.resolve_members(methods)?;
+
+                self.current_class = enclosing_class;
+                self.class_name = enclosing_name;
+            }
+            Stmt::VariableDeclaration(name, _, initializer) => {
                 self.declare(name)?;
                 if **initializer != Expr::NilLiteral {
-                    self.resolve_expression(initializer)?;
+                    self.resolve_awaitable(initializer)?;
                 }
                 self.define(name);
             }
-            Stmt::FunctionDeclaration(name, _, _) => {
+            Stmt::FunctionDeclaration(name, _, _, _) => {
                 self.declare(name)?;
                 self.define(name);
 
                 self.resolve_function(statement, &FunctionType::Function)?;
             }
-            Stmt::Return(_, value) => {
+            Stmt::Return(keyword, value) => {
                 if self.current_function == FunctionType::None {
-                    return Err("Can't return from top level code".to_string());
+                    return Err(error(keyword, "Can't return from top level code"));
                 }
                 if **value != Expr::NilLiteral {
                     if self.current_function == FunctionType::Initializer {
-                        return Err("Cant return a value from an initializer".to_string());
+                        return Err(error(keyword, "Cant return a value from an initializer"));
                     }
-                    self.resolve_expression(value)?;
+                    self.resolve_awaitable(value)?;
+                }
+            }
+            Stmt::Yield(keyword, value) => {
+                if self.current_function == FunctionType::None {
+                    return Err(error(keyword, "Can't yield from top level code"));
+                }
+                if self.current_function == FunctionType::Initializer {
+                    return Err(error(keyword, "Can't yield from an initializer"));
+                }
+                self.resolve_expression(value)?;
+            }
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => {
+                if !self.in_loop {
+                    return Err(error(
+                        keyword,
+                        &format!("Can't use '{}' outside of a loop", keyword.lexeme),
+                    ));
                 }
             }
         }
         Ok(())
     }
 
+    // the whole of an expression statement, initializer or returned value, which
+    // can be an await
+    fn resolve_awaitable(&self, expression: &Expr) -> Result<(), String> {
+        self.await_allowed
+            .set(matches!(expression, Expr::Await(_, _)));
+        self.resolve_expression(expression)
+    }
+
+    fn resolve_loop_body(&mut self, body: &Stmt) -> Result<(), String> {
+        let enclosing_loop = self.in_loop;
+        self.in_loop = true;
+        let resolved = self.resolve_statement(body);
+        self.in_loop = enclosing_loop;
+        resolved
+    }
+
     fn resolve_expression(&self, expression: &Expr) -> Result<(), String> {
         // println!("resolving: {}", expression);
         match expression {
@@ -150,18 +228,31 @@ impl<'a> Resolver<'a> {
                 self.resolve_expression(value)?;
                 self.resolve_local(expression, name);
             }
-            Expr::Binary(left, _, right) => {
+            Expr::Binary(left, _, right) | Expr::Index(left, _, right) => {
                 self.resolve_expression(left)?;
                 self.resolve_expression(right)?;
             }
             Expr::Grouping(expression) => {
                 self.resolve_expression(expression)?;
             }
-            Expr::Urnary(_, right) => {
+            Expr::Urnary(_, right) | Expr::Spawn(_, right) => {
+                self.resolve_expression(right)?;
+            }
+            // a function could be spawned, and a task can only stop at an await it
+            // can pick up from; top level code never runs in a task
+            Expr::Await(keyword, right) => {
+                if !self.await_allowed.replace(false) && self.current_function != FunctionType::None
+                {
+                    return Err(error(
+                        keyword,
+                        "Can only await in a statement of its own, a variable's initializer or a return",
+                    ));
+                }
                 self.resolve_expression(right)?;
             }
             Expr::StringLiteral(_) => (),
-            Expr::NumberLiteral(_) => (),
+            Expr::NumberLiteral(..) => (),
+            Expr::IntLiteral(..) => (),
             Expr::NilLiteral => (),
             Expr::TrueLiteral => (),
             Expr::FalseLiteral => (),
@@ -169,7 +260,8 @@ impl<'a> Resolver<'a> {
                 if !self.scopes.is_empty()
                     && self.scopes.last().unwrap().get(&name.lexeme) == Some(&false)
                 {
-                    return Err(String::from(
+                    return Err(error(
+                        name,
                         "Can't read local variable in its own initializer",
                     ));
                 }
@@ -185,26 +277,57 @@ impl<'a> Resolver<'a> {
                     self.resolve_expression(argument)?;
                 }
             }
-            Expr::Get(object, _) => {
+            Expr::Get(object, name) | Expr::OptionalGet(object, name) => {
+                self.check_private(object, name)?;
                 self.resolve_expression(object)?;
             }
-            Expr::Set(object, _, value) => {
+            Expr::Conditional(condition, _, then_branch, else_branch) => {
+                self.resolve_expression(condition)?;
+                self.resolve_expression(then_branch)?;
+                self.resolve_expression(else_branch)?;
+            }
+            Expr::Set(object, name, value) => {
+                self.check_private(object, name)?;
+                self.resolve_expression(object)?;
+                self.resolve_expression(value)?;
+            }
+            Expr::CompoundAssign(name, _, value) => {
+                self.resolve_expression(value)?;
+                self.resolve_local(expression, name);
+            }
+            Expr::CompoundSet(object, name, _, value) => {
+                self.check_private(object, name)?;
                 self.resolve_expression(object)?;
                 self.resolve_expression(value)?;
             }
+            Expr::Postfix(update, _) => {
+                self.resolve_expression(update)?;
+            }
             Expr::This(keyword) => match self.current_class {
                 ClassType::None => {
-                    return Err("Can't use this outside of a class".to_string());
+                    return Err(error(keyword, "Can't use this outside of a class"));
+                }
+                _ if self.in_static_method => {
+                    return Err(error(keyword, "Can't use 'this' in a static method"));
                 }
                 _ => {
                     self.resolve_local(expression, keyword);
                 }
             },
             Expr::Super(keyword, _) => match self.current_class {
-                ClassType::None => return Err("Can't use 'super' outside of a class".to_string()),
+                ClassType::None => {
+                    return Err(error(keyword, "Can't use 'super' outside of a class"))
+                }
+                _ if self.in_static_method => {
+                    return Err(error(keyword, "Can't use 'super' in a static method"));
+                }
                 ClassType::Class => {
-                    return Err("Can't use 'super' in a class with no superclass".to_string())
+                    return Err(error(
+                        keyword,
+                        "Can't use 'super' in a class with no superclass",
+                    ))
                 }
+                ClassType::Trait => return Err(error(keyword, "Can't use 'super' in a trait")),
                 ClassType::Subclass => {
                     self.resolve_local(expression, keyword);
                 }
@@ -219,9 +342,48 @@ impl<'a> Resolver<'a> {
             if scope.contains_key(&name.lexeme) {
                 self.interpreter
                     .resolve(expression, self.scopes.len() - 1 - i);
+                let declaration = self.declarations[i].get(&name.lexeme).cloned();
+                self.references
+                    .borrow_mut()
+                    .push((name.clone(), declaration));
                 return;
             }
         }
+        self.references.borrow_mut().push((name.clone(), None));
+    }
+
+    fn resolve_members(&mut self, members: &[Stmt]) -> Result<(), String> {
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert("this".to_string(), true);
+
+        for member in members {
+            // field defaults run like a method with no parameters
+            if let Stmt::VariableDeclaration(_, _, initializer) = member {
+                let enclosing_function = self.current_function;
+                self.current_function = FunctionType::Method;
+                self.begin_scope();
+                self.resolve_expression(initializer)?;
+                self.end_scope();
+                self.current_function = enclosing_function;
+            }
+            if let Stmt::FunctionDeclaration(method_name, _, _, signature) = member {
+                let function_type = match signature.kind {
+                    MethodKind::Static => FunctionType::StaticMethod,
+                    MethodKind::Method if method_name.lexeme == "init" => FunctionType::Initializer,
+                    _ => FunctionType::Method,
+                };
+                let enclosing_static = self.in_static_method;
+                self.in_static_method = function_type == FunctionType::StaticMethod;
+                self.resolve_function(member, &function_type)?;
+                self.in_static_method = enclosing_static;
+            }
+        }
+
+        self.end_scope();
+        Ok(())
     }
 
     fn resolve_function(
@@ -229,9 +391,11 @@ impl<'a> Resolver<'a> {
         function: &Stmt,
         function_type: &FunctionType,
     ) -> Result<(), String> {
-        let enclosing_function = function_type;
+        let enclosing_function = self.current_function;
+        let enclosing_loop = self.in_loop;
+        self.in_loop = false;
         self.begin_scope();
-        if let Stmt::FunctionDeclaration(_, params, body) = function {
+        if let Stmt::FunctionDeclaration(_, params, body, _) = function {
             self.current_function = *function_type;
             for param in params {
                 self.declare(param)?;
@@ -239,19 +403,55 @@ impl<'a> Resolver<'a> {
             }
             self.resolve(body)?;
             self.end_scope();
-            self.current_function = *enclosing_function;
+            self.current_function = enclosing_function;
+            self.in_loop = enclosing_loop;
             Ok(())
         } else {
             Err(format!("Unexpected statement {}", function))
         }
     }
 
+    // "_name" and "#name" members can only be used from inside a class body, through
+    // 'this' or, for static members, the class's own name
+    fn check_private(&self, object: &Expr, name: &Token) -> Result<(), String> {
+        let is_private = name.lexeme.starts_with('_') || name.lexeme.starts_with('#');
+        if !is_private {
+            return Ok(());
+        }
+        if self.current_class == ClassType::None {
+            return Err(error(
+                name,
+                &format!(
+                    "Can't access private member '{}' outside of a class",
+                    name.lexeme
+                ),
+            ));
+        }
+        let owned = match object {
+            Expr::This(_) => true,
+            Expr::Variable(class) => self.class_name.as_ref() == Some(&class.lexeme),
+            _ => false,
+        };
+        if !owned {
+            return Err(error(
+                name,
+                &format!(
+                    "Can only access private member '{}' through 'this'",
+                    name.lexeme
+                ),
+            ));
+        }
+        Ok(())
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.declarations.push(HashMap::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.declarations.pop();
     }
 
     fn declare(&mut self, name: &Token) -> Result<(), String> {
@@ -260,7 +460,10 @@ impl<'a> Resolver<'a> {
         }
 
         if self.scopes.last().unwrap().contains_key(&name.lexeme) {
-            return Err("Variable with this name already exists in this scope.".to_string());
+            return Err(error(
+                name,
+                "Variable with this name already exists in this scope.",
+            ));
         }
 
         // means that the variable assignment exists and we know about it
@@ -268,6 +471,10 @@ impl<'a> Resolver<'a> {
             .last_mut()
             .unwrap()
             .insert(name.lexeme.clone(), false);
+        self.declarations
+            .last_mut()
+            .unwrap()
+            .insert(name.lexeme.clone(), name.clone());
 
         Ok(())
     }
@@ -284,3 +491,7 @@ impl<'a> Resolver<'a> {
             .insert(name.lexeme.clone(), true);
     }
 }
+
+fn error(token: &Token, message: &str) -> String {
+    format!("Line {}: {}", token.line, message)
+}
@@ -9,21 +9,30 @@ lazy_static! {
     static ref KEYWORDS_MAP: HashMap<&'static str, TokenType> = {
         let mut m = HashMap::new();
         m.insert("and", TokenType::And);
+        m.insert("await", TokenType::Await);
+        m.insert("break", TokenType::Break);
         m.insert("class", TokenType::Class);
+        m.insert("continue", TokenType::Continue);
         m.insert("else", TokenType::Else);
         m.insert("false", TokenType::False);
         m.insert("for", TokenType::For);
         m.insert("fun", TokenType::Fun);
         m.insert("if", TokenType::If);
+        m.insert("in", TokenType::In);
+        m.insert("is", TokenType::Is);
         m.insert("nil", TokenType::Nil);
         m.insert("or", TokenType::Or);
         m.insert("print", TokenType::Print);
         m.insert("return", TokenType::Return);
+        m.insert("spawn", TokenType::Spawn);
         m.insert("super", TokenType::Super);
         m.insert("this", TokenType::This);
+        m.insert("trait", TokenType::Trait);
         m.insert("true", TokenType::True);
         m.insert("var", TokenType::Var);
         m.insert("while", TokenType::While);
+        m.insert("with", TokenType::With);
+        m.insert("yield", TokenType::Yield);
         m
     };
 }
@@ -31,9 +40,14 @@ lazy_static! {
 pub struct Scanner<'a> {
     pub source: &'a str,
     tokens: Vec<Token>,
+    comments: Vec<Token>,
     start: usize,
     current: usize,
     line: i32,
+    // byte offsets of where the current line and token begin on their line
+    line_start: usize,
+    start_column: usize,
+    unterminated_string: bool,
 }
 
 impl<'a> Scanner<'a> {
@@ -41,9 +55,13 @@ impl<'a> Scanner<'a> {
         Self {
             source,
             tokens: Vec::new(),
+            comments: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_column: 0,
+            unterminated_string: false,
         }
     }
 
@@ -51,13 +69,26 @@ impl<'a> Scanner<'a> {
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, String> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_column = self.current - self.line_start;
             self.scan_token()?;
         }
-        self.tokens
-            .push(Token::new(TokenType::Eof, "".to_string(), None, self.line));
+        let mut eof = Token::new(TokenType::Eof, "".to_string(), None, self.line);
+        eof.column = self.current - self.line_start;
+        self.tokens.push(eof);
         Ok(self.tokens.clone())
     }
 
+    // comments seen by scan_tokens, the parser never sees these
+    pub fn comments(&self) -> Vec<Token> {
+        self.comments.clone()
+    }
+
+    // true if scanning failed because a string was still open at the end,
+    // i.e. more input could close it
+    pub fn hit_unterminated_string(&self) -> bool {
+        self.unterminated_string
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
@@ -68,14 +99,53 @@ impl<'a> Scanner<'a> {
         let maybe_token = match ch {
             '(' => self.generate_token_option(TokenType::LeftParen),
             ')' => self.generate_token_option(TokenType::RightParen),
+            '[' => self.generate_token_option(TokenType::LeftBracket),
+            ']' => self.generate_token_option(TokenType::RightBracket),
             '{' => self.generate_token_option(TokenType::LeftBrace),
             '}' => self.generate_token_option(TokenType::RightBrace),
+            ':' => self.generate_token_option(TokenType::Colon),
             ',' => self.generate_token_option(TokenType::Comma),
             '.' => self.generate_token_option(TokenType::Dot),
-            '-' => self.generate_token_option(TokenType::Minus),
-            '+' => self.generate_token_option(TokenType::Plus),
+            '-' => Some(if self.match_char('=') {
+                self.generate_new_token(TokenType::MinusEqual)
+            } else if self.match_char('-') {
+                self.generate_new_token(TokenType::MinusMinus)
+            } else {
+                self.generate_new_token(TokenType::Minus)
+            }),
+            '+' => Some(if self.match_char('=') {
+                self.generate_new_token(TokenType::PlusEqual)
+            } else if self.match_char('+') {
+                self.generate_new_token(TokenType::PlusPlus)
+            } else {
+                self.generate_new_token(TokenType::Plus)
+            }),
             ';' => self.generate_token_option(TokenType::Semicolon),
-            '*' => self.generate_token_option(TokenType::Star),
+            '&' => self.generate_token_option(TokenType::Ampersand),
+            // integer division, "//" is always a comment
+            '\\' => self.generate_token_option(TokenType::Backslash),
+            '|' => self.generate_token_option(TokenType::Pipe),
+            '^' => self.generate_token_option(TokenType::Caret),
+            '~' => self.generate_token_option(TokenType::Tilde),
+            '?' => Some(if self.match_char('?') {
+                self.generate_new_token(TokenType::QuestionQuestion)
+            } else if self.match_char('.') {
+                self.generate_new_token(TokenType::QuestionDot)
+            } else {
+                self.generate_new_token(TokenType::Question)
+            }),
+            '*' => Some(if self.match_char('=') {
+                self.generate_new_token(TokenType::StarEqual)
+            } else if self.match_char('*') {
+                self.generate_new_token(TokenType::StarStar)
+            } else {
+                self.generate_new_token(TokenType::Star)
+            }),
+            '%' => Some(if self.match_char('=') {
+                self.generate_new_token(TokenType::PercentEqual)
+            } else {
+                self.generate_new_token(TokenType::Percent)
+            }),
             '!' => Some(if self.match_char('=') {
                 self.generate_new_token(TokenType::BangEqual)
             } else {
@@ -88,11 +158,15 @@ impl<'a> Scanner<'a> {
             }),
             '<' => Some(if self.match_char('=') {
                 self.generate_new_token(TokenType::LessEqual)
+            } else if self.match_char('<') {
+                self.generate_new_token(TokenType::LessLess)
             } else {
                 self.generate_new_token(TokenType::Less)
             }),
             '>' => Some(if self.match_char('=') {
                 self.generate_new_token(TokenType::GreaterEqual)
+            } else if self.match_char('>') {
+                self.generate_new_token(TokenType::GreaterGreater)
             } else {
                 self.generate_new_token(TokenType::Greater)
             }),
@@ -101,7 +175,12 @@ impl<'a> Scanner<'a> {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    let mut comment = self.generate_new_token(TokenType::Comment);
+                    comment.column = self.start_column;
+                    self.comments.push(comment);
                     None
+                } else if self.match_char('=') {
+                    self.generate_token_option(TokenType::SlashEqual)
                 } else {
                     self.generate_token_option(TokenType::Slash)
                 }
@@ -109,22 +188,27 @@ impl<'a> Scanner<'a> {
             // ignore whitespace
             ' ' | '\r' | '\t' => None,
             '\n' => {
-                self.line += 1;
+                self.new_line();
                 None
             }
             '"' => {
                 let result_string = self.string()?;
                 Some(result_string)
             }
-            '0'..='9' => self.number(),
+            '0'..='9' => Some(self.number()?),
             'a'..='z' | 'A'..='Z' | '_' => self.identifier(),
-            _ch => {
-                return Err(String::from("unrecognized character"));
+            // "#name" is a private member, scanned as a name that keeps its '#'
+            '#' if Self::is_alpha(self.peek()) => self.identifier(),
+            ch => {
+                return Err(format!(
+                    "Line {}: Unrecognized character '{}'",
+                    self.line, ch
+                ));
             }
         };
 
-        if maybe_token.is_some() {
-            self.add_token(maybe_token.unwrap());
+        if let Some(token) = maybe_token {
+            self.add_token(token);
         };
         Ok(())
     }
@@ -138,25 +222,30 @@ impl<'a> Scanner<'a> {
     }
 
     // guts
+    // self.current is a byte offset, so multi-byte chars move it by more than 1
     fn advance(&mut self) -> char {
         let ch = self.get_current_char();
-        self.current += 1;
+        self.current += ch.len_utf8();
         ch
     }
 
     fn get_current_char(&self) -> char {
-        let ch = self
-            .source
+        self.source[self.current..]
             .chars()
-            .nth(self.current)
-            .expect("self.current is greater than the number of chars in self.source");
-        ch
+            .next()
+            .expect("self.current is greater than the number of chars in self.source")
     }
 
-    fn add_token(&mut self, token: Token) {
+    fn add_token(&mut self, mut token: Token) {
+        token.column = self.start_column;
         self.tokens.push(token);
     }
 
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
     fn get_lexeme(&self) -> String {
         let range = self.start..self.current;
         self.source
@@ -173,7 +262,7 @@ impl<'a> Scanner<'a> {
             return false;
         }
 
-        self.current += 1;
+        self.current += ch.len_utf8();
         true
     }
 
@@ -186,62 +275,103 @@ impl<'a> Scanner<'a> {
     }
 
     fn peek_next(&self) -> char {
-        let next_index = self.current + 1;
-        if next_index >= self.source.len() {
+        if self.is_at_end() {
             '\0'
         } else {
-            self.source
-                .chars()
-                .nth(next_index)
-                .expect("self.current + 1 is greater than the number of chars in self.source")
+            self.source[self.current..].chars().nth(1).unwrap_or('\0')
         }
     }
 
     fn is_digit(ch: char) -> bool {
-        ch >= '0' && ch <= '9'
+        ch.is_ascii_digit()
     }
 
     fn is_alpha(ch: char) -> bool {
-        (ch >= 'a' && ch <= 'z') || (ch >= 'A' && ch <= 'Z') || (ch == '_')
+        ch.is_ascii_alphabetic() || ch == '_'
     }
 
     fn is_alphanumeric(ch: char) -> bool {
         Self::is_alpha(ch) || Self::is_digit(ch)
     }
 
-    fn number(&mut self) -> Option<Token> {
-        while Self::is_digit(self.peek()) {
+    fn number(&mut self) -> Result<Token, String> {
+        // 0xff and 0b1010, which are always integers
+        let radix = match (self.get_lexeme().as_str(), self.peek()) {
+            ("0", 'x' | 'X') if self.peek_next().is_ascii_hexdigit() => 16,
+            ("0", 'b' | 'B') if matches!(self.peek_next(), '0' | '1') => 2,
+            _ => 10,
+        };
+        if radix != 10 {
             self.advance();
+            self.digits(radix);
+            let number_literal = self.get_lexeme();
+            let value = self.int_literal(&number_literal[2..], radix)?;
+            return Ok(Token::new(
+                TokenType::Number,
+                number_literal,
+                Some(Literal::Int(value)),
+                self.line,
+            ));
         }
 
+        self.digits(10);
+
         // can't do is_numeric because we can only have 1 decimal point in a number
         if self.peek() == '.' && Self::is_digit(self.peek_next()) {
             self.advance();
-
-            while Self::is_digit(self.peek()) {
-                self.advance();
-            }
+            self.digits(10);
+
+            let number_literal = self.get_lexeme();
+            return Ok(Token::new(
+                TokenType::Number,
+                number_literal.clone(),
+                Some(Literal::Number(
+                    number_literal.replace('_', "").parse::<f64>().unwrap(),
+                )),
+                self.line,
+            ));
         }
 
         let number_literal = self.get_lexeme();
-        Some(Token::new(
+        let value = self.int_literal(&number_literal, 10)?;
+        Ok(Token::new(
             TokenType::Number,
-            number_literal.clone(),
-            Some(Literal::Number(number_literal.parse::<f64>().unwrap())),
+            number_literal,
+            Some(Literal::Int(value)),
             self.line,
         ))
     }
 
+    // digits in the given radix, with underscores allowed between them as in 1_000_000
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix)
+            || (self.peek() == '_' && self.peek_next().is_digit(radix))
+        {
+            self.advance();
+        }
+    }
+
+    fn int_literal(&self, digits: &str, radix: u32) -> Result<i64, String> {
+        i64::from_str_radix(&digits.replace('_', ""), radix).map_err(|_| {
+            format!(
+                "Line {}: Integer literal {} is too large",
+                self.line,
+                self.get_lexeme()
+            )
+        })
+    }
+
     fn string(&mut self) -> Result<Token, String> {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let ch = self.advance();
+            if ch == '\n' {
+                self.new_line();
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            return Err("Unterminated string".to_string());
+            self.unterminated_string = true;
+            return Err(format!("Line {}: Unterminated string", self.line));
         }
 
         // swallow closing quotes
@@ -4,46 +4,120 @@ use super::{expr::Expr, token::Token};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Stmt {
-    Block(Vec<Stmt>),
-    Expression(Box<Expr>),
-    If(Box<Expr>, Box<Stmt>, Box<Option<Stmt>>),
-    Print(Box<Expr>),
-    While(Box<Expr>, Box<Stmt>),
-    ClassDeclaration(Box<Token>, Option<Expr>, Vec<Stmt>),
-    VariableDeclaration(Box<Token>, Box<Expr>),
-    FunctionDeclaration(Token, Vec<Token>, Vec<Stmt>),
+    // the leading token of each statement is kept for its line number
+    Block(Token, Vec<Stmt>),
+    Expression(Token, Box<Expr>),
+    If(Token, Box<Expr>, Box<Stmt>, Box<Option<Stmt>>),
+    Print(Token, Box<Expr>),
+    While(Token, Box<Expr>, Box<Stmt>),
+    // "for (var name in iterable) body", the token is the 'for' keyword
+    ForIn(Token, Box<Token>, Box<Expr>, Box<Stmt>),
+    // members are methods, and fields as variable declarations
+    // the expressions after the superclass are the traits mixed in with "with"
+    ClassDeclaration(Box<Token>, Option<Expr>, Vec<Expr>, Vec<Stmt>),
+    // a trait only has methods, which are copied into the classes using it
+    TraitDeclaration(Box<Token>, Vec<Stmt>),
+    // the optional token is the type annotation
+    VariableDeclaration(Box<Token>, Option<Token>, Box<Expr>),
+    FunctionDeclaration(Token, Vec<Token>, Vec<Stmt>, Signature),
     Return(Token, Box<Expr>),
+    // a function with one of these in its body is a generator
+    Yield(Token, Box<Expr>),
+    // leave or skip to the next pass of the innermost loop
+    Break(Token),
+    Continue(Token),
+}
+
+// the optional type annotations of a function, one per parameter, and its return type,
+// along with what kind of class member it is
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Signature {
+    pub parameters: Vec<Option<Token>>,
+    pub returns: Option<Token>,
+    pub kind: MethodKind,
+}
+
+// functions outside classes are plain methods as far as this goes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MethodKind {
+    #[default]
+    Method,
+    // "class name() {}", called on the class itself
+    Static,
+    // "name {}", run when the property is read
+    Getter,
+    // "set name(value) {}", run when the property is assigned
+    Setter,
+}
+
+impl Stmt {
+    // line the statement starts on
+    pub fn line(&self) -> i32 {
+        match self {
+            Stmt::Block(token, _)
+            | Stmt::Expression(token, _)
+            | Stmt::If(token, _, _, _)
+            | Stmt::Print(token, _)
+            | Stmt::While(token, _, _)
+            | Stmt::ForIn(token, _, _, _)
+            | Stmt::FunctionDeclaration(token, _, _, _)
+            | Stmt::Return(token, _)
+            | Stmt::Yield(token, _)
+            | Stmt::Break(token)
+            | Stmt::Continue(token) => token.line,
+            Stmt::ClassDeclaration(name, _, _, _)
+            | Stmt::TraitDeclaration(name, _)
+            | Stmt::VariableDeclaration(name, _, _) => name.line,
+        }
+    }
 }
 
 impl Display for Stmt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Stmt::Block(stmts) => {
+            Stmt::Block(_, stmts) => {
                 write!(f, "(block")?;
                 for stmt in stmts {
                     write!(f, " {}", stmt)?;
                 }
                 write!(f, ")")
             }
-            Stmt::Expression(expr) => write!(f, "(expression {})", expr),
-            Stmt::If(condition, then_branch, else_branch) => {
+            Stmt::Expression(_, expr) => write!(f, "(expression {})", expr),
+            Stmt::If(_, condition, then_branch, else_branch) => {
                 write!(f, "(if {} then {}", condition, then_branch)?;
                 if let Some(else_stmt) = else_branch.as_ref() {
                     write!(f, " else {}", else_stmt)?;
                 }
                 write!(f, ")")
             }
-            Stmt::Print(expr) => write!(f, "(print {})", expr),
-            Stmt::While(condition, body) => write!(f, "(while {} do {})", condition, body),
-            Stmt::VariableDeclaration(name, expr) => write!(f, "(var {} = {})", name.lexeme, expr),
-            Stmt::ClassDeclaration(name, superclass, methods) => {
+            Stmt::Print(_, expr) => write!(f, "(print {})", expr),
+            Stmt::Break(_) => write!(f, "(break)"),
+            Stmt::Continue(_) => write!(f, "(continue)"),
+            Stmt::While(_, condition, body) => write!(f, "(while {} do {})", condition, body),
+            Stmt::ForIn(_, name, iterable, body) => {
+                write!(f, "(for {} in {} do {})", name.lexeme, iterable, body)
+            }
+            Stmt::VariableDeclaration(name, _, expr) => {
+                write!(f, "(var {} = {})", name.lexeme, expr)
+            }
+            Stmt::ClassDeclaration(name, superclass, traits, methods) => {
                 write!(f, "(class {} < {:?}", name.lexeme, superclass)?;
+                for name in traits {
+                    write!(f, " with {}", name)?;
+                }
+                for method in methods {
+                    write!(f, " {}", method)?;
+                }
+                write!(f, ")")
+            }
+            Stmt::TraitDeclaration(name, methods) => {
+                write!(f, "(trait {}", name.lexeme)?;
                 for method in methods {
                     write!(f, " {}", method)?;
                 }
                 write!(f, ")")
             }
-            Stmt::FunctionDeclaration(name, params, stmts) => {
+            Stmt::FunctionDeclaration(name, params, stmts, _) => {
                 write!(f, "(fun {} = (", name)?;
                 for param in params {
                     write!(f, " {}", param)?;
@@ -55,6 +129,7 @@ impl Display for Stmt {
                 write!(f, "))")
             }
             Stmt::Return(_, value) => write!(f, "(return {})", value),
+            Stmt::Yield(_, value) => write!(f, "(yield {})", value),
         }
     }
 }
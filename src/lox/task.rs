@@ -0,0 +1,199 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use super::{callable::NativeFunction, generator::Generator, value::Value};
+
+// what spawn, sleep and receive give, finishing with a value that await hands back
+#[derive(Clone)]
+pub struct Task {
+    name: String,
+    state: Rc<RefCell<TaskState>>,
+}
+
+struct TaskState {
+    // what a spawned task runs, timers and receives finish without running anything
+    body: Option<Generator>,
+    result: Option<Value>,
+    // tasks suspended until this one finishes
+    waiting: Vec<Task>,
+}
+
+impl Task {
+    pub fn new(name: String, body: Option<Generator>) -> Self {
+        Self {
+            name,
+            state: Rc::new(RefCell::new(TaskState {
+                body,
+                result: None,
+                waiting: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn body(&self) -> Option<Generator> {
+        self.state.borrow().body.clone()
+    }
+
+    pub fn result(&self) -> Option<Value> {
+        self.state.borrow().result.clone()
+    }
+}
+
+impl Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<task {}>", self.name)
+    }
+}
+
+impl Debug for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(task {})", self.name)
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+// decides which task runs next, one at a time and always in the same order; time
+// is counted rather than waited for, so sleeping only orders tasks
+#[derive(Default)]
+pub struct Scheduler {
+    // milliseconds slept so far
+    now: i64,
+    // tasks that can go on, with the value to go on with
+    ready: VecDeque<(Task, Value)>,
+    // sleeps by when they end, ties in the order they started
+    timers: BTreeMap<(i64, usize), Task>,
+    started: usize,
+    // whether a task's body is running, as a task can't block on another
+    pub running: bool,
+}
+
+impl Scheduler {
+    pub fn spawn(&mut self, task: Task) {
+        self.ready.push_back((task, Value::Nil));
+    }
+
+    pub fn sleep(&mut self, milliseconds: i64) -> Result<Task, String> {
+        let wakes = self.now.checked_add(milliseconds).ok_or_else(|| {
+            format!(
+                "Can't sleep {}ms, the clock is already at {}ms",
+                milliseconds, self.now
+            )
+        })?;
+        let task = Task::new("sleep".to_string(), None);
+        self.timers.insert((wakes, self.started), task.clone());
+        self.started += 1;
+        Ok(task)
+    }
+
+    // finishes a task, letting whatever awaits it go on
+    pub fn finish(&mut self, task: &Task, value: Value) {
+        let waiting = {
+            let mut state = task.state.borrow_mut();
+            state.result = Some(value.clone());
+            std::mem::take(&mut state.waiting)
+        };
+        for waiter in waiting {
+            self.ready.push_back((waiter, value.clone()));
+        }
+    }
+
+    // suspends a task until another has finished
+    pub fn wait(&mut self, waiter: Task, task: &Task) {
+        match task.result() {
+            Some(value) => self.ready.push_back((waiter, value)),
+            None => task.state.borrow_mut().waiting.push(waiter),
+        }
+    }
+
+    // the next task to run, moving time on to the next sleep to end if none are ready
+    pub fn next(&mut self) -> Option<(Task, Value)> {
+        while self.ready.is_empty() {
+            let ((end, _), timer) = self.timers.pop_first()?;
+            self.now = self.now.max(end);
+            self.finish(&timer, Value::Nil);
+        }
+        self.ready.pop_front()
+    }
+}
+
+// passes values between tasks, first in first out; sending never waits
+#[derive(Clone, Default)]
+pub struct Channel {
+    state: Rc<RefCell<ChannelState>>,
+}
+
+#[derive(Default)]
+struct ChannelState {
+    values: VecDeque<Value>,
+    // receives waiting for a value, oldest first
+    receiving: VecDeque<Task>,
+}
+
+impl Channel {
+    // receive() gives a task to await rather than a value, as there may not be one yet
+    pub fn method(&self, name: &str) -> Option<NativeFunction> {
+        let method = match name {
+            "send" => NativeFunction::new("send".to_string(), 1, |interpreter, arguments| {
+                let channel = receiver(&arguments);
+                let value = arguments[1].clone();
+                let receiving = channel.state.borrow_mut().receiving.pop_front();
+                match receiving {
+                    Some(task) => interpreter.scheduler().finish(&task, value),
+                    None => channel.state.borrow_mut().values.push_back(value),
+                }
+                Ok(Value::Nil)
+            }),
+            "receive" => NativeFunction::new("receive".to_string(), 0, |interpreter, arguments| {
+                let channel = receiver(&arguments);
+                let task = Task::new("receive".to_string(), None);
+                let value = channel.state.borrow_mut().values.pop_front();
+                match value {
+                    Some(value) => interpreter.scheduler().finish(&task, value),
+                    None => channel.state.borrow_mut().receiving.push_back(task.clone()),
+                }
+                Ok(Value::Task(task))
+            }),
+            _ => return None,
+        };
+        Some(method.bind(Value::Channel(self.clone())))
+    }
+}
+
+// send and receive are bound to the channel, which comes first
+fn receiver(arguments: &[Value]) -> &Channel {
+    match arguments.first() {
+        Some(Value::Channel(channel)) => channel,
+        _ => unreachable!("send and receive are only bound to channels"),
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<channel>")
+    }
+}
+
+impl Debug for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(channel {})", self.state.borrow().values.len())
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
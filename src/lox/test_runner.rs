@@ -0,0 +1,231 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use super::{stmt::Stmt, Lox};
+
+// what a test file says should happen when it runs, read from comments like
+//   print 1 + 2; // expect: 3
+//   var a = a; // expect error: Can't read local variable in its own initializer
+//   nil.foo; // expect runtime error: Only instances have properties
+#[derive(Default)]
+struct Expectations {
+    output: Vec<String>,
+    // static errors, as the front end reports them ("Line 3: ...")
+    errors: Vec<String>,
+    // runtime errors, reported at the line that raised them ("[line 3] ...")
+    runtime_error: Option<String>,
+}
+
+const EXPECT_OUTPUT: &str = "// expect: ";
+const EXPECT_ERROR: &str = "// expect error: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+
+const UNIT_TEST_PREFIX: &str = "test_";
+
+// exit codes of a lox run, see Lox::run
+const STATIC_ERROR: i32 = 65;
+const RUNTIME_ERROR: i32 = 70;
+
+// runs every .lox file in the given files and directories, tests/ by default,
+// along with the unit tests in them, returns false if any of them failed
+pub fn run_tests(paths: &[String], optimize: bool) -> bool {
+    let mut files = Vec::new();
+    if paths.is_empty() {
+        collect_files(Path::new("tests"), &mut files);
+    }
+    for path in paths {
+        collect_files(Path::new(path), &mut files);
+    }
+
+    let lox = match env::current_exe() {
+        Ok(lox) => lox,
+        Err(err) => {
+            println!("Could not find the lox executable: {}", err);
+            return false;
+        }
+    };
+
+    let (mut passed, mut failed) = (0, 0);
+    for file in &files {
+        let failures = match run_test(&lox, file, optimize) {
+            Ok(failures) => failures,
+            Err(err) => vec![err],
+        };
+        report(
+            &file.display().to_string(),
+            &failures,
+            &mut passed,
+            &mut failed,
+        );
+
+        for (name, result) in run_unit_tests(file, optimize) {
+            let failures = result.err().into_iter().collect::<Vec<String>>();
+            let test = format!("{} {}", file.display(), name);
+            report(&test, &failures, &mut passed, &mut failed);
+        }
+    }
+    println!(
+        "{} passed, {} failed, {} total",
+        passed,
+        failed,
+        passed + failed
+    );
+    failed == 0
+}
+
+fn report(test: &str, failures: &[String], passed: &mut usize, failed: &mut usize) {
+    if failures.is_empty() {
+        *passed += 1;
+        return;
+    }
+    *failed += 1;
+    println!("FAIL {}", test);
+    for failure in failures {
+        println!("     {}", failure);
+    }
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries = match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<PathBuf>>(),
+            Err(err) => {
+                println!("Could not read '{}': {}", path.display(), err);
+                return;
+            }
+        };
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files);
+        }
+    } else if path.extension().is_some_and(|extension| extension == "lox") {
+        files.push(path.to_path_buf());
+    }
+}
+
+// each test runs in its own process, so one can't affect another
+// returns what went wrong, empty if it passed
+fn run_test(lox: &Path, file: &Path, optimize: bool) -> Result<Vec<String>, String> {
+    let source = fs::read_to_string(file)
+        .map_err(|err| format!("Could not read '{}': {}", file.display(), err))?;
+    let expected = parse_expectations(&source);
+    let mut command = Command::new(lox);
+    if !optimize {
+        command.arg("--no-optimize");
+    }
+    let output = command
+        .arg(file)
+        .output()
+        .map_err(|err| format!("Could not run '{}': {}", file.display(), err))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let code = output.status.code().unwrap_or(-1);
+
+    let mut failures = Vec::new();
+    let (expected_code, expected_errors) = if !expected.errors.is_empty() {
+        (STATIC_ERROR, expected.errors.clone())
+    } else if let Some(runtime_error) = &expected.runtime_error {
+        (RUNTIME_ERROR, vec![runtime_error.clone()])
+    } else {
+        (0, Vec::new())
+    };
+    compare(
+        "output",
+        &expected.output,
+        &stdout.lines().collect::<Vec<&str>>(),
+        &mut failures,
+    );
+    compare(
+        "error",
+        &expected_errors,
+        &stderr.lines().collect::<Vec<&str>>(),
+        &mut failures,
+    );
+    if code != expected_code {
+        failures.push(format!(
+            "Expected exit code {} but got {}",
+            expected_code, code
+        ));
+    }
+    Ok(failures)
+}
+
+// top level functions named test_* that take no arguments are unit tests,
+// each one runs in a fresh interpreter once the rest of the file has run
+fn run_unit_tests(file: &Path, optimize: bool) -> Vec<(String, Result<(), String>)> {
+    // files that don't read or parse have already failed their golden test
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(_) => return Vec::new(),
+    };
+    let stmts = match Lox::parse_source(&source) {
+        Ok(stmts) => stmts,
+        Err(_) => return Vec::new(),
+    };
+    stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDeclaration(name, parameters, _, _)
+                if name.lexeme.starts_with(UNIT_TEST_PREFIX) && parameters.is_empty() =>
+            {
+                Some(name.lexeme.clone())
+            }
+            _ => None,
+        })
+        .map(|name| {
+            let result = run_unit_test(&source, &name, optimize);
+            (name, result)
+        })
+        .collect()
+}
+
+fn run_unit_test(source: &str, name: &str, optimize: bool) -> Result<(), String> {
+    let mut lox = Lox::new();
+    lox.set_optimize(optimize);
+    // the golden test already checks what the file prints
+    lox.interpreter.set_output(Box::new(io::sink()));
+    let stmts = Lox::parse_source(source)?;
+    lox.analyze(&stmts)?;
+    let stmts = lox.optimize(stmts)?;
+    lox.interpreter.interpret(stmts)?;
+    let test = lox.interpreter.globals.borrow().get(name.to_string())?;
+    test.call(&mut lox.interpreter, Vec::new())?;
+    Ok(())
+}
+
+fn parse_expectations(source: &str) -> Expectations {
+    let mut expectations = Expectations::default();
+    for (index, line) in source.lines().enumerate() {
+        if let Some((_, output)) = line.split_once(EXPECT_OUTPUT) {
+            expectations.output.push(output.to_string());
+        } else if let Some((_, error)) = line.split_once(EXPECT_ERROR) {
+            expectations
+                .errors
+                .push(format!("Line {}: {}", index + 1, error));
+        } else if let Some((_, error)) = line.split_once(EXPECT_RUNTIME_ERROR) {
+            expectations.runtime_error = Some(format!("[line {}] {}", index + 1, error));
+        }
+    }
+    expectations
+}
+
+fn compare(kind: &str, expected: &[String], actual: &[&str], failures: &mut Vec<String>) {
+    for (index, expected_line) in expected.iter().enumerate() {
+        match actual.get(index) {
+            Some(actual_line) if actual_line == expected_line => (),
+            Some(actual_line) => failures.push(format!(
+                "Expected {} '{}' but got '{}'",
+                kind, expected_line, actual_line
+            )),
+            None => failures.push(format!("Missing expected {} '{}'", kind, expected_line)),
+        }
+    }
+    for extra in actual.iter().skip(expected.len()) {
+        failures.push(format!("Unexpected {} '{}'", kind, extra));
+    }
+}
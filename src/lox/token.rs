@@ -6,6 +6,7 @@ use super::token_type::TokenType;
 pub enum Literal {
     String(String),
     Number(f64),
+    Int(i64),
 }
 
 impl Display for Literal {
@@ -13,6 +14,7 @@ impl Display for Literal {
         match self {
             Literal::String(literal) => write!(f, "(string - {}", literal),
             Literal::Number(literal) => write!(f, "(number - {}", literal),
+            Literal::Int(literal) => write!(f, "(int - {}", literal),
         }
     }
 }
@@ -23,6 +25,8 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<Literal>,
     pub line: i32,
+    // byte offset into the line, filled in by the scanner
+    pub column: usize,
 }
 
 impl std::hash::Hash for Token {
@@ -42,8 +46,62 @@ impl Token {
             lexeme,
             literal,
             line,
+            column: 0,
         }
     }
+
+    // the binary operator a compound assignment or increment applies, '+' for "+=" and "++"
+    pub fn compound_operator(&self) -> Token {
+        let (token_type, lexeme) = match self.token_type {
+            TokenType::PlusEqual | TokenType::PlusPlus => (TokenType::Plus, "+"),
+            TokenType::MinusEqual | TokenType::MinusMinus => (TokenType::Minus, "-"),
+            TokenType::StarEqual => (TokenType::Star, "*"),
+            TokenType::SlashEqual => (TokenType::Slash, "/"),
+            TokenType::PercentEqual => (TokenType::Percent, "%"),
+            _ => return self.clone(),
+        };
+        Token {
+            token_type,
+            lexeme: lexeme.to_string(),
+            ..self.clone()
+        }
+    }
+
+    pub fn is_increment(&self) -> bool {
+        matches!(self.token_type, TokenType::PlusPlus | TokenType::MinusMinus)
+    }
+
+    // the method an instance defines to overload this binary operator
+    pub fn overload(&self) -> Option<Overload> {
+        let (method, swapped, negated) = match self.token_type {
+            TokenType::Plus => ("__add__", false, false),
+            TokenType::Minus => ("__sub__", false, false),
+            TokenType::Star => ("__mul__", false, false),
+            TokenType::Slash => ("__div__", false, false),
+            TokenType::Percent => ("__mod__", false, false),
+            TokenType::EqualEqual => ("__eq__", false, false),
+            TokenType::BangEqual => ("__eq__", false, true),
+            TokenType::Less => ("__lt__", false, false),
+            // "a > b" is "b < a", "a <= b" is "!(b < a)" and "a >= b" is "!(a < b)"
+            TokenType::Greater => ("__lt__", true, false),
+            TokenType::LessEqual => ("__lt__", true, true),
+            TokenType::GreaterEqual => ("__lt__", false, true),
+            _ => return None,
+        };
+        Some(Overload {
+            method,
+            swapped,
+            negated,
+        })
+    }
+}
+
+pub struct Overload {
+    pub method: &'static str,
+    // called on the right operand, with the left one as its argument
+    pub swapped: bool,
+    // the result is the opposite of what the method returns
+    pub negated: bool,
 }
 
 impl Display for Token {
@@ -1,17 +1,27 @@
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
 pub enum TokenType {
     // Single-character tokens.
+    Ampersand,
+    Backslash,
+    Caret,
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
+    Percent,
+    Pipe,
     Plus,
+    Question,
     Semicolon,
     Slash,
     Star,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -22,6 +32,18 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    MinusEqual,
+    MinusMinus,
+    PercentEqual,
+    PlusEqual,
+    PlusPlus,
+    QuestionDot,
+    QuestionQuestion,
+    SlashEqual,
+    StarEqual,
+    StarStar,
 
     // Literals.
     Identifier,
@@ -30,21 +52,33 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Await,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
+    Is,
     Nil,
     Or,
     Print,
     Return,
+    Spawn,
     Super,
     This,
+    Trait,
     True,
     Var,
     While,
+    With,
+    Yield,
+
+    // Trivia, kept out of the token stream.
+    Comment,
 
     Eof,
 }
@@ -1,22 +1,60 @@
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    ops::RangeInclusive,
+};
 
 use super::{
     callable::{Callable, Function, NativeFunction},
-    class::Class,
+    class::{Class, Trait},
+    generator::Generator,
     instance::Instance,
     interpreter::Interpreter,
+    task::{Channel, Task},
 };
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum Value {
     Number(f64),
+    Int(i64),
     String(String),
     Bool(bool),
     Nil,
     NativeFunction(NativeFunction),
     Function(Function),
     Class(Class),
+    Trait(Trait),
     Instance(Instance),
+    Range(Range),
+    Generator(Generator),
+    Task(Task),
+    Channel(Channel),
+}
+
+// what range(start, end, step) gives, counting from start up to but not including end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+impl Range {
+    pub fn values(self) -> impl Iterator<Item = i64> {
+        let mut next = Some(self.start);
+        std::iter::from_fn(move || {
+            let value = next?;
+            let in_range = if self.step > 0 {
+                value < self.end
+            } else {
+                value > self.end
+            };
+            if !in_range {
+                return None;
+            }
+            next = value.checked_add(self.step);
+            Some(value)
+        })
+    }
 }
 
 impl Value {
@@ -36,6 +74,51 @@ impl Value {
         }
     }
 
+    // the name users see in messages about a value's type
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) | Value::Int(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "boolean",
+            Value::Nil => "nil",
+            Value::NativeFunction(_) | Value::Function(_) => "function",
+            Value::Class(_) => "class",
+            Value::Trait(_) => "trait",
+            Value::Range(_) => "range",
+            Value::Generator(_) => "generator",
+            Value::Task(_) => "task",
+            Value::Channel(_) => "channel",
+            Value::Instance(_) => "instance",
+        }
+    }
+
+    // like type_name, but names the class of an instance
+    pub fn describe_type(&self) -> String {
+        match self {
+            Value::Instance(instance) => format!("instance {}", instance.class()),
+            _ => self.type_name().to_string(),
+        }
+    }
+
+    // only instances belong to a class, through their own class or its superclasses
+    pub fn is_instance_of(&self, class: &Class) -> bool {
+        match self {
+            Value::Instance(instance) => instance.class().is_subclass_of(class),
+            _ => false,
+        }
+    }
+
+    // the numbers of arguments it can be called with, None when it can't be called
+    pub fn arities(&self) -> Option<RangeInclusive<usize>> {
+        let arity = match self {
+            Value::NativeFunction(function) => return Some(function.arities()),
+            Value::Function(function) => function.arity(),
+            Value::Class(class) => class.arity(),
+            _ => return None,
+        };
+        Some(arity..=arity)
+    }
+
     pub fn bind(&self, instance: &Instance) -> Result<Value, String> {
         if let Value::Function(method) = self {
             method.bind(instance)
@@ -45,6 +128,46 @@ impl Value {
     }
 }
 
+// ints and floats are both numbers, so 1 == 1.0
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(int), Value::Number(number)) | (Value::Number(number), Value::Int(int)) => {
+                int_equals_float(*int, *number)
+            }
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Nil, Value::Nil) => true,
+            (Value::NativeFunction(left), Value::NativeFunction(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => left == right,
+            (Value::Class(left), Value::Class(right)) => left == right,
+            (Value::Trait(left), Value::Trait(right)) => left == right,
+            (Value::Range(left), Value::Range(right)) => left == right,
+            (Value::Generator(left), Value::Generator(right)) => left == right,
+            (Value::Task(left), Value::Task(right)) => left == right,
+            (Value::Channel(left), Value::Channel(right)) => left == right,
+            (Value::Instance(left), Value::Instance(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+// exact, so a float only equals an int it represents without rounding
+fn int_equals_float(int: i64, number: f64) -> bool {
+    number.fract() == 0.0
+        && number >= i64::MIN as f64
+        && number < i64::MAX as f64
+        && number as i64 == int
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
 impl From<bool> for Value {
     fn from(v: bool) -> Self {
         Self::Bool(v)
@@ -70,12 +193,20 @@ impl Display for Value {
             "{}",
             match self {
                 Value::Number(number_value) => number_value.to_string(),
+                Value::Int(int_value) => int_value.to_string(),
                 Value::String(string_value) => string_value.to_string(),
                 Value::Bool(bool_value) => bool_value.to_string(),
                 Value::Nil => String::from("nil"),
                 Value::NativeFunction(callable) => format!("{}", callable),
                 Value::Function(callable) => format!("{}", callable),
                 Value::Class(klass) => format!("{}", klass),
+                Value::Trait(mixed) => format!("{}", mixed),
+                Value::Generator(generator) => format!("{}", generator),
+                Value::Task(task) => format!("{}", task),
+                Value::Channel(channel) => format!("{}", channel),
+                Value::Range(range) => {
+                    format!("range({}, {}, {})", range.start, range.end, range.step)
+                }
                 Value::Instance(instance) => format!("{}", instance),
             }
         )
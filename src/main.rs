@@ -5,9 +5,17 @@ mod java_class_generator;
 mod lox;
 
 use std::process::exit;
-use std::{env, io::Result};
+use std::{
+    env, fs,
+    io::{self, Result},
+};
 
 use java_class_generator::define_ast;
+use lox::dap::Adapter;
+use lox::formatter::format_files;
+use lox::linter::lint_files;
+use lox::lsp::Server;
+use lox::test_runner::run_tests;
 use lox::Lox;
 
 use lox::expr::Expr;
@@ -16,7 +24,7 @@ use lox::token::Token;
 use lox::token_type::TokenType;
 
 fn main() -> Result<()> {
-    let lox = Lox {};
+    let mut lox = Lox::new();
     let args: Vec<String> = env::args().collect();
     // different from go, first arg is always binary in rust
     if args.len() > 2 {
@@ -56,6 +64,23 @@ fn main() -> Result<()> {
                     ],
                 )?
             }
+            "--coverage" => lox.coverage_file(&args[2]),
+            "--no-check" => {
+                lox.set_check(false);
+                lox.run_file(&args[2])
+            }
+            "--no-optimize" => {
+                lox.set_optimize(false);
+                lox.run_file(&args[2])
+            }
+            "--profile" => lox.profile_file(&args[2]),
+            "dap" => dap()?,
+            "debug" => lox.debug_file(&args[2]),
+            "fmt" => fmt(&args[2..])?,
+            "lint" => lint(&args[2..])?,
+            // editors usually pass --stdio, which is the only transport anyway
+            "lsp" => lsp()?,
+            "test" => test(&args[2..]),
             _ => {
                 println!("Usage: jlox [script]");
                 exit(64)
@@ -64,14 +89,19 @@ fn main() -> Result<()> {
     } else if args.len() == 2 {
         // lend args[1] to run_file
         match args[1].as_str() {
+            "dap" => dap()?,
+            "fmt" => fmt(&[])?,
+            "lint" => lint(&[])?,
+            "lsp" => lsp()?,
+            "test" => test(&[]),
             "ast" => {
                 let expression = Expr::Binary(
                     Box::from(Expr::Urnary(
                         Token::new(TokenType::Minus, "-".to_string(), None, 1),
-                        Box::from(Expr::NumberLiteral(123 as f64)),
+                        Box::from(Expr::NumberLiteral(123_f64, None)),
                     )),
                     Token::new(TokenType::Star, "*".to_string(), None, 1),
-                    Box::from(Expr::Grouping(Box::from(Expr::NumberLiteral(45.67 as f64)))),
+                    Box::from(Expr::Grouping(Box::from(Expr::NumberLiteral(45.67, None)))),
                 );
                 print!("{}", expression);
             }
@@ -82,3 +112,63 @@ fn main() -> Result<()> {
     }
     Ok(())
 }
+
+// lox fmt [--check] [files...]
+fn fmt(args: &[String]) -> Result<()> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "--check")
+        .cloned()
+        .collect();
+    if !format_files(&lox_files(files)?, check) {
+        exit(1);
+    }
+    Ok(())
+}
+
+// lox lint [files...]
+fn lint(args: &[String]) -> Result<()> {
+    if !lint_files(&lox_files(args.to_vec())?) {
+        exit(1);
+    }
+    Ok(())
+}
+
+// lox lsp, speaks the language server protocol over stdin and stdout
+fn lsp() -> Result<()> {
+    let code = Server::new(io::stdin().lock(), io::stdout()).run()?;
+    exit(code)
+}
+
+// lox dap, a debug adapter over stdin and stdout
+fn dap() -> Result<()> {
+    Adapter::new(io::stdin().lock(), io::stdout()).run()
+}
+
+// lox test [--no-optimize] [files or directories...], runs tests/ by default
+fn test(args: &[String]) {
+    let optimize = !args.iter().any(|arg| arg == "--no-optimize");
+    let paths: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "--no-optimize")
+        .cloned()
+        .collect();
+    if !run_tests(&paths, optimize) {
+        exit(1);
+    }
+}
+
+// with no files given, every .lox file in the current directory is used
+fn lox_files(mut files: Vec<String>) -> Result<Vec<String>> {
+    if files.is_empty() {
+        for entry in fs::read_dir(".")? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "lox") {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+        files.sort();
+    }
+    Ok(files)
+}
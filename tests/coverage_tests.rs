@@ -0,0 +1,84 @@
+use std::{fs, path::Path, process::Command};
+
+const SOURCE: &str = "\
+fun half(n) {
+  if (n > 10) {
+    return n / 2;
+  } else {
+    return n;
+  }
+}
+fun unused() {
+  print \"never\";
+}
+var i = 0;
+while (i < 3) i = i + 1;
+print half(4) or false;
+print nil and true;
+";
+
+#[test]
+fn lcov_report() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("coverage");
+    fs::create_dir_all(&scratch).unwrap();
+    fs::write(scratch.join("cov.lox"), SOURCE).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .args(["--coverage", "cov.lox"])
+        .current_dir(&scratch)
+        .output()
+        .expect("could not run the interpreter");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "4\nnil\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "\
+Lines: 8/10 (80.0%)
+Branches: 4/8 (50.0%)
+Functions: 1/2 (50.0%)
+Coverage written to lcov.info
+"
+    );
+
+    // functions, then branches as line,block,arm,taken, then lines as line,hits
+    let path = fs::canonicalize(scratch.join("cov.lox")).unwrap();
+    let expected = format!(
+        "\
+TN:
+SF:{}
+FN:1,half:1
+FN:8,unused:8
+FNDA:1,half:1
+FNDA:0,unused:8
+FNF:2
+FNH:1
+BRDA:2,0,0,0
+BRDA:2,0,1,1
+BRDA:12,1,0,1
+BRDA:12,1,1,0
+BRDA:13,2,0,1
+BRDA:13,2,1,0
+BRDA:14,3,0,1
+BRDA:14,3,1,0
+BRF:8
+BRH:4
+DA:1,1
+DA:2,1
+DA:3,0
+DA:5,1
+DA:8,1
+DA:9,0
+DA:11,1
+DA:12,4
+DA:13,1
+DA:14,1
+LF:10
+LH:8
+end_of_record
+",
+        path.display()
+    );
+    assert_eq!(
+        fs::read_to_string(scratch.join("lcov.info")).unwrap(),
+        expected
+    );
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use serde_json::{json, Value as Json};
+
+const SOURCE: &str = "\
+fun outer() {
+  var depth = \"outer\";
+  return inner(depth);
+}
+fun inner(label) {
+  var depth = \"inner\";
+  return label + \" \" + depth;
+}
+print outer();
+print \"done\";
+";
+
+// sends the requests to 'lox dap' in one go, as the adapter reads them in order
+// whether or not the program is paused, and gives back everything it sent
+fn run_session(requests: &[(&str, Json)]) -> Vec<Json> {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dap");
+    fs::create_dir_all(&scratch).unwrap();
+    let program = scratch.join("depth.lox");
+    fs::write(&program, SOURCE).unwrap();
+
+    let mut bytes = Vec::new();
+    for (seq, (command, arguments)) in requests.iter().enumerate() {
+        let mut arguments = arguments.clone();
+        if *command == "launch" || *command == "setBreakpoints" {
+            arguments["program"] = json!(program);
+            arguments["source"] = json!({ "path": program });
+        }
+        let body = json!({
+            "seq": seq + 1,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(bytes, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+
+    let mut adapter = Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .arg("dap")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("could not run the interpreter");
+    adapter.stdin.take().unwrap().write_all(&bytes).unwrap();
+    let output = adapter.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let mut messages = Vec::new();
+    let mut rest = String::from_utf8(output.stdout).unwrap();
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .expect("missing Content-Length")
+            .parse()
+            .unwrap();
+        messages.push(serde_json::from_str(&body[..length]).unwrap());
+        rest = body[length..].to_string();
+    }
+    messages
+}
+
+fn response(messages: &[Json], request_seq: usize) -> &Json {
+    messages
+        .iter()
+        .find(|message| message["type"] == "response" && message["request_seq"] == request_seq)
+        .unwrap_or_else(|| panic!("no response to request {}", request_seq))
+}
+
+fn events<'a>(messages: &'a [Json], event: &str) -> Vec<&'a Json> {
+    messages
+        .iter()
+        .filter(|message| message["type"] == "event" && message["event"] == event)
+        .map(|message| &message["body"])
+        .collect()
+}
+
+#[test]
+fn scripted_session() {
+    let messages = run_session(&[
+        ("initialize", json!({ "adapterID": "lox" })),
+        ("launch", json!({})),
+        ("setBreakpoints", json!({ "breakpoints": [{ "line": 7 }] })),
+        ("configurationDone", json!({})),
+        // paused on line 7, inside inner called from outer
+        ("stackTrace", json!({ "threadId": 1 })),
+        ("scopes", json!({ "frameId": 2 })),
+        ("variables", json!({ "variablesReference": 1 })),
+        ("evaluate", json!({ "expression": "depth", "frameId": 2 })),
+        ("evaluate", json!({ "expression": "depth", "frameId": 1 })),
+        ("evaluate", json!({ "expression": "depth" })),
+        ("next", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("disconnect", json!({})),
+    ]);
+
+    assert_eq!(events(&messages, "initialized").len(), 1);
+    assert_eq!(
+        response(&messages, 3)["body"]["breakpoints"],
+        json!([{ "verified": true, "line": 7 }])
+    );
+
+    let stopped = events(&messages, "stopped");
+    assert_eq!(stopped.len(), 2);
+    assert_eq!(stopped[0]["reason"], "breakpoint");
+    assert_eq!(stopped[1]["reason"], "step");
+
+    let frames = &response(&messages, 5)["body"]["stackFrames"];
+    let frames: Vec<(i64, &str, i64)> = frames
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|frame| {
+            (
+                frame["id"].as_i64().unwrap(),
+                frame["name"].as_str().unwrap(),
+                frame["line"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        vec![(2, "inner", 7), (1, "outer", 3), (0, "script", 9)]
+    );
+
+    let scopes = &response(&messages, 6)["body"]["scopes"];
+    assert_eq!(scopes[0]["name"], "Locals");
+    assert_eq!(scopes[0]["variablesReference"], 1);
+
+    let variables = &response(&messages, 7)["body"]["variables"];
+    assert_eq!(
+        variables,
+        &json!([
+            { "name": "depth", "value": "inner", "type": "string", "variablesReference": 0 },
+            { "name": "label", "value": "outer", "type": "string", "variablesReference": 0 },
+        ])
+    );
+
+    // each frame sees its own variables
+    assert_eq!(response(&messages, 8)["body"]["result"], "inner");
+    assert_eq!(response(&messages, 9)["body"]["result"], "outer");
+    let global = response(&messages, 10);
+    assert_eq!(global["success"], false);
+    assert_eq!(global["message"], "Undefined variable 'depth'");
+
+    let output: String = events(&messages, "output")
+        .iter()
+        .map(|body| body["output"].as_str().unwrap())
+        .collect();
+    assert_eq!(output, "outer inner\ndone\n");
+    assert_eq!(events(&messages, "exited")[0]["exitCode"], 0);
+    assert_eq!(events(&messages, "terminated").len(), 1);
+}
@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+const SOURCE: &str = "\
+fun add(a, b) {
+  var sum = a + b;
+  return sum;
+}
+var total = 0;
+for (var i = 1; i <= 3; i = i + 1) {
+  total = add(total, i);
+}
+print total;
+";
+
+// runs the script under 'lox debug', typing the commands at its prompt; each test
+// gets its own directory, as they run at the same time
+fn debug(test: &str, commands: &str) -> Output {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    fs::create_dir_all(&scratch).unwrap();
+    fs::write(scratch.join("counter.lox"), SOURCE).unwrap();
+    let mut debugger = Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .args(["debug", "counter.lox"])
+        .current_dir(&scratch)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("could not run the interpreter");
+    debugger
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    debugger.wait_with_output().unwrap()
+}
+
+#[test]
+fn breakpoints_locals_and_backtrace() {
+    let output = debug(
+        "debug_breakpoints",
+        "break 2\ncontinue\nlocals\nprint a * 10 + b\nbacktrace\nfinish\nnext\ndelete 2\ncontinue\n",
+    );
+    let expected = "\
+Debugging counter.lox, type 'help' for commands
+[line 1] fun add(a, b) {
+(debug) Breakpoint set at line 2
+(debug) Breakpoint at line 2
+[line 2] var sum = a + b;
+(debug) scope 0:
+  a = 0
+  b = 1
+(debug) 1
+(debug) #0 add at line 2
+#1 script at line 7
+(debug) [line 6] for (var i = 1; i <= 3; i = i + 1) {
+(debug) [line 7] total = add(total, i);
+(debug) Breakpoint at line 2 removed
+(debug) 6
+Program finished
+";
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+// step enters the call that next steps over
+#[test]
+fn step_and_next() {
+    let output = debug(
+        "debug_step",
+        "break 7\ncontinue\nstep\nnext\nnext\nnext\nquit\n",
+    );
+    let expected = "\
+Debugging counter.lox, type 'help' for commands
+[line 1] fun add(a, b) {
+(debug) Breakpoint set at line 7
+(debug) Breakpoint at line 7
+[line 7] total = add(total, i);
+(debug) [line 2] var sum = a + b;
+(debug) [line 3] return sum;
+(debug) [line 6] for (var i = 1; i <= 3; i = i + 1) {
+(debug) [line 7] total = add(total, i);
+(debug) ";
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+fn lox(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("could not run the interpreter")
+}
+
+// every script under tests/lox that parses, copied into a scratch directory so
+// formatting them in place leaves the originals alone
+fn scratch_scripts(name: &str) -> (PathBuf, Vec<String>) {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&scratch);
+    let mut scripts = Vec::new();
+    let mut directories = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox")];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                directories.push(path);
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            // syntax errors can't be formatted, and static errors point at lines
+            // that formatting may move
+            if source.contains("// expect error: ") {
+                continue;
+            }
+            let relative = path
+                .strip_prefix(env!("CARGO_MANIFEST_DIR"))
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            let copy = scratch.join(&relative);
+            fs::create_dir_all(copy.parent().unwrap()).unwrap();
+            fs::write(&copy, source).unwrap();
+            scripts.push(relative);
+        }
+    }
+    scripts.sort();
+    (scratch, scripts)
+}
+
+// formatting keeps every script's behaviour, including its `// expect` comments,
+// and formatting a second time changes nothing
+#[test]
+fn formatting_round_trips() {
+    let (scratch, scripts) = scratch_scripts("fmt_round_trip");
+    let scripts: Vec<&str> = scripts.iter().map(|script| script.as_str()).collect();
+
+    let output = lox(&[&["fmt"], scripts.as_slice()].concat(), &scratch);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = lox(
+        &[&["fmt", "--check"], scripts.as_slice()].concat(),
+        &scratch,
+    );
+    assert!(
+        output.status.success(),
+        "not idempotent:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = lox(&["test", "tests/lox"], &scratch);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn check_reports_unformatted_files() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fmt_check");
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).unwrap();
+    let source = "fun  add(a,b){return a+b;}\nprint add(1,2); // three\n";
+    fs::write(scratch.join("messy.lox"), source).unwrap();
+
+    let output = lox(&["fmt", "--check", "messy.lox"], &scratch);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Would reformat messy.lox\n"
+    );
+    assert_eq!(
+        fs::read_to_string(scratch.join("messy.lox")).unwrap(),
+        source
+    );
+
+    let output = lox(&["fmt", "messy.lox"], &scratch);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(scratch.join("messy.lox")).unwrap(),
+        "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2); // three\n"
+    );
+}
+
+// numbers keep the spelling they were written with
+#[test]
+fn number_literals_keep_their_spelling() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fmt_numbers");
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).unwrap();
+    let source = "\
+print 0xFF_FF;
+print 0b1010;
+print 1_000_000;
+print 1_000.50;
+print 2.0;
+print 7;
+";
+    fs::write(scratch.join("numbers.lox"), source).unwrap();
+
+    let output = lox(&["fmt", "--check", "numbers.lox"], &scratch);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = lox(&["fmt", "numbers.lox"], &scratch);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(scratch.join("numbers.lox")).unwrap(),
+        source
+    );
+}
@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+// lints source written to a scratch file named after the test
+fn lint(name: &str, source: &str) -> Output {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("lint");
+    fs::create_dir_all(&scratch).unwrap();
+    fs::write(scratch.join(format!("{}.lox", name)), source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .args(["lint", &format!("{}.lox", name)])
+        .current_dir(&scratch)
+        .output()
+        .expect("could not run the interpreter")
+}
+
+// warnings on the same line come out in the order the names appear
+#[test]
+fn unused_variables_in_source_order() {
+    let source =
+        "fun f(a, b, c, d, e) {\n  var x = 1; var y = 2; var z = 3;\n}\nf(1, 2, 3, 4, 5);\n";
+    let expected = "\
+unused.lox:1: warning[L002] unused parameter 'a' (unused-parameter)
+unused.lox:1: warning[L002] unused parameter 'b' (unused-parameter)
+unused.lox:1: warning[L002] unused parameter 'c' (unused-parameter)
+unused.lox:1: warning[L002] unused parameter 'd' (unused-parameter)
+unused.lox:1: warning[L002] unused parameter 'e' (unused-parameter)
+unused.lox:2: warning[L001] unused variable 'x' (unused-variable)
+unused.lox:2: warning[L001] unused variable 'y' (unused-variable)
+unused.lox:2: warning[L001] unused variable 'z' (unused-variable)
+";
+    for _ in 0..5 {
+        let output = lint("unused", source);
+        assert!(!output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+}
+
+// a variable compared to itself is constant, but properties aren't, as getters and
+// __eq__ overloads run code that can answer differently each time
+#[test]
+fn comparing_a_variable_to_itself_is_constant() {
+    let source = "\
+class P {
+  v { return clock(); }
+  __eq__(_other) { return false; }
+}
+var p = P();
+var x = p;
+print p.v == p.v;
+print x == x;
+print (x) < x;
+print 1 == 1;
+";
+    let output = lint("self_comparison", source);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "self_comparison.lox:8: warning[L006] 'x == x' is always true (constant-comparison)\n\
+         self_comparison.lox:9: warning[L006] '(x) < x' is always false (constant-comparison)\n\
+         self_comparison.lox:10: warning[L006] '1 == 1' is always true (constant-comparison)\n"
+    );
+}
+
+// ints beyond 2^53 compare exactly instead of through floats
+#[test]
+fn large_int_comparisons_are_exact() {
+    let source = "\
+print 9007199254740993 == 9007199254740992;
+print 9007199254740993 > 9007199254740992;
+";
+    let output = lint("large_ints", source);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "large_ints.lox:1: warning[L006] '9007199254740993 == 9007199254740992' is always false (constant-comparison)\n\
+         large_ints.lox:2: warning[L006] '9007199254740993 > 9007199254740992' is always true (constant-comparison)\n"
+    );
+}
+
+// break and continue end a block as return does, but a for loop's increment still
+// runs after continue
+#[test]
+fn statements_after_break_and_continue_are_unreachable() {
+    let source = "\
+while (true) {
+  break;
+  print 1;
+}
+for (var i = 0; i < 3; i = i + 1) {
+  continue;
+  print i;
+}
+for (var j = 0; j < 3; j = j + 1) continue;
+";
+    let output = lint("loop_exits", source);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "loop_exits.lox:3: warning[L004] unreachable statement after 'break' (unreachable-code)\n\
+         loop_exits.lox:7: warning[L004] unreachable statement after 'continue' (unreachable-code)\n"
+    );
+}
@@ -0,0 +1,99 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+fn lox_test(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .arg("test")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("could not run the interpreter")
+}
+
+// runs every script under tests/lox through `lox test`, which checks each one
+// against its `// expect` comments
+#[test]
+fn lox_scripts() {
+    let output = lox_test(&["tests/lox"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+}
+
+// the same expectations hold with constant folding and dead branch elimination off
+#[test]
+fn lox_scripts_unoptimized() {
+    let output = lox_test(&["--no-optimize", "tests/lox"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+}
+
+// a failing test_* function is reported with the line of the failed assertion, and
+// fails the run; it lives outside tests/lox, which has to pass
+#[test]
+fn failing_unit_tests() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("unit_tests");
+    fs::create_dir_all(&scratch).unwrap();
+    let source = "\
+fun double(n) {
+  return n * 2;
+}
+
+print double(2); // expect: 4
+
+fun test_double_passes() {
+  assert_eq(double(3), 6);
+}
+
+fun test_double_fails() {
+  var doubled = double(5);
+  assert_eq(doubled, 11);
+}
+
+fun test_assert_fails() {
+  assert(double(1) > 2, \"double(1) is more than 2\");
+}
+";
+    let file = scratch.join("units.lox");
+    fs::write(&file, source).unwrap();
+
+    let output = lox_test(&[file.to_str().unwrap()]);
+    let expected = format!(
+        "\
+FAIL {file} test_double_fails
+     [line 13] Assertion failed: expected 11 but got 10
+FAIL {file} test_assert_fails
+     [line 17] Assertion failed: double(1) is more than 2
+2 passed, 2 failed, 4 total
+",
+        file = file.display()
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+// a program the checker rejects still runs, and fails only where it goes wrong
+#[test]
+fn unchecked_scripts_run() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_check");
+    fs::create_dir_all(&scratch).unwrap();
+    let source = "print \"before\";\nprint 1 + \"one\";\n";
+    fs::write(scratch.join("mixed.lox"), source).unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+            .args(args)
+            .current_dir(&scratch)
+            .output()
+            .expect("could not run the interpreter")
+    };
+
+    let checked = run(&["mixed.lox"]);
+    assert!(!checked.status.success());
+    assert_eq!(String::from_utf8_lossy(&checked.stdout), "");
+
+    let unchecked = run(&["--no-check", "mixed.lox"]);
+    assert!(!unchecked.status.success());
+    assert_eq!(String::from_utf8_lossy(&unchecked.stdout), "before\n");
+}
@@ -0,0 +1,169 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use serde_json::{json, Value as Json};
+
+const URI: &str = "file:///scratch/pets.lox";
+
+const SOURCE: &str = "\
+class Animal {}
+class Dog < Animal {
+  speak(times) { return times; }
+}
+fun add(a, b) { return a + b; }
+var total = add(1, 2);
+print add(total, 3);
+";
+
+// frames each message with its Content-Length header, as a client would
+fn script(messages: &[Json]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        write!(bytes, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    bytes
+}
+
+// everything the server wrote, split back into messages, and its exit code
+fn run_session(messages: &[Json]) -> (Vec<Json>, Option<i32>) {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("could not run the interpreter");
+    server
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&script(messages))
+        .unwrap();
+    let output = server.wait_with_output().unwrap();
+
+    let mut replies = Vec::new();
+    let mut rest = String::from_utf8(output.stdout).unwrap();
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .expect("missing Content-Length")
+            .parse()
+            .unwrap();
+        replies.push(serde_json::from_str(&body[..length]).unwrap());
+        rest = body[length..].to_string();
+    }
+    (replies, output.status.code())
+}
+
+fn request(id: i32, method: &str, params: Json) -> Json {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn at(line: i32, character: i32) -> Json {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+fn reply(replies: &[Json], id: i32) -> &Json {
+    let reply = replies
+        .iter()
+        .find(|reply| reply["id"] == id)
+        .unwrap_or_else(|| panic!("no reply to request {}", id));
+    &reply["result"]
+}
+
+fn location(line: i32, start: i32, end: i32) -> Json {
+    json!({
+        "uri": URI,
+        "range": {
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end },
+        },
+    })
+}
+
+#[test]
+fn scripted_session() {
+    let mut references = at(4, 4);
+    references["context"] = json!({ "includeDeclaration": true });
+    let (replies, code) = run_session(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+        notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": SOURCE } }),
+        ),
+        // the call to add on the last line
+        request(2, "textDocument/definition", at(6, 7)),
+        request(3, "textDocument/references", references),
+        request(4, "textDocument/hover", at(1, 6)),
+        request(5, "textDocument/hover", at(5, 13)),
+        request(6, "shutdown", Json::Null),
+        notification("exit", Json::Null),
+    ]);
+
+    let capabilities = &reply(&replies, 1)["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["referencesProvider"], true);
+    assert_eq!(capabilities["hoverProvider"], true);
+
+    let diagnostics = replies
+        .iter()
+        .find(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .expect("no diagnostics published");
+    assert_eq!(diagnostics["params"]["uri"], URI);
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    assert_eq!(reply(&replies, 2), &json!([location(4, 4, 7)]));
+    assert_eq!(
+        reply(&replies, 3),
+        &json!([location(4, 4, 7), location(5, 12, 15), location(6, 6, 9)])
+    );
+    assert_eq!(
+        reply(&replies, 4)["contents"]["value"],
+        "```lox\nclass Dog < Animal\n```"
+    );
+    assert_eq!(
+        reply(&replies, 5)["contents"]["value"],
+        "```lox\nfun add(a, b)\narity 2\n```"
+    );
+    assert_eq!(reply(&replies, 6), &Json::Null);
+    assert_eq!(code, Some(0));
+}
+
+#[test]
+fn diagnostics_follow_changes() {
+    let (replies, code) = run_session(&[
+        notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": SOURCE } }),
+        ),
+        notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": "print 1;\nreturn 2;\n" }],
+            }),
+        ),
+        notification("exit", Json::Null),
+    ]);
+
+    let published: Vec<&Json> = replies
+        .iter()
+        .filter(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .map(|reply| &reply["params"]["diagnostics"])
+        .collect();
+    assert_eq!(published.len(), 2);
+    assert_eq!(published[0], &json!([]));
+    let diagnostics = published[1].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    // exit without shutdown
+    assert_eq!(code, Some(1));
+}
@@ -0,0 +1,96 @@
+use std::{collections::HashMap, fs, path::Path, process::Command};
+
+const SOURCE: &str = "\
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+fun add(a, b) { return a + b; }
+var total = 0;
+for (var i = 0; i < 50; i = i + 1) total = add(total, fib(8));
+print total;
+";
+
+// count, inclusive and exclusive milliseconds by the name at the end of each row
+// in one section of the report
+fn section(report: &str, title: &str) -> HashMap<String, (usize, f64, f64)> {
+    let mut rows = HashMap::new();
+    let lines = report.lines().skip_while(|line| *line != title).skip(2);
+    for line in lines {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        // a section ends at a blank line or whatever follows the report
+        let count = match columns.first().map(|count| count.parse::<usize>()) {
+            Some(Ok(count)) => count,
+            _ => break,
+        };
+        let millis = |column: &str| column.trim_end_matches("ms").parse::<f64>().unwrap();
+        rows.insert(
+            columns[3].to_string(),
+            (count, millis(columns[1]), millis(columns[2])),
+        );
+    }
+    rows
+}
+
+#[test]
+fn report_and_collapsed_stacks() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("profile");
+    fs::create_dir_all(&scratch).unwrap();
+    fs::write(scratch.join("fib.lox"), SOURCE).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_crafting-interpreter"))
+        .args(["--profile", "fib.lox"])
+        .current_dir(&scratch)
+        .output()
+        .expect("could not run the interpreter");
+    assert!(output.status.success());
+    // the report stays out of the program's output
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1050\n");
+
+    let report = String::from_utf8_lossy(&output.stderr);
+    let functions = section(&report, "Functions by exclusive time");
+    let lines = section(&report, "Lines by exclusive time");
+    let counts = |rows: &HashMap<String, (usize, f64, f64)>| {
+        let mut counts: Vec<(String, usize)> = rows
+            .iter()
+            .map(|(name, (count, _, _))| (name.clone(), *count))
+            .collect();
+        counts.sort();
+        counts
+    };
+    assert_eq!(
+        counts(&functions),
+        vec![
+            ("add:5".to_string(), 50),
+            ("fib:1".to_string(), 3350),
+            ("script".to_string(), 1),
+        ]
+    );
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines["3"].0, 1650);
+
+    // time spent in something can't be more than the time it was running
+    for (name, (_, inclusive, exclusive)) in functions.iter().chain(lines.iter()) {
+        assert!(
+            exclusive <= inclusive,
+            "{} has {}ms exclusive but {}ms inclusive",
+            name,
+            exclusive,
+            inclusive
+        );
+    }
+
+    let folded = fs::read_to_string(scratch.join("fib.lox.folded")).unwrap();
+    assert!(report.contains("Collapsed stacks written to fib.lox.folded"));
+    for line in folded.lines() {
+        let (stack, micros) = line.rsplit_once(' ').unwrap();
+        assert!(
+            stack == "script" || stack.starts_with("script;"),
+            "{}",
+            line
+        );
+        assert!(micros.parse::<u128>().unwrap() > 0, "{}", line);
+    }
+    assert!(folded
+        .lines()
+        .any(|line| line.starts_with("script;fib:1;fib:1 ")));
+}